@@ -0,0 +1,76 @@
+use std::env;
+use std::ffi::OsStr;
+use std::io::{self, IsTerminal};
+
+#[derive(Clone, Copy)]
+pub enum Color {
+    Yellow,
+    Green,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Color::Yellow => "33",
+            Color::Green => "32",
+        }
+    }
+}
+
+/// When to color the output. `Auto` colors it only when stdout is a terminal
+/// and NO_COLOR isn't set to a non-empty value (see no-color.org).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+pub fn enabled(choice: ColorChoice) -> bool {
+    resolve(
+        choice,
+        io::stdout().is_terminal(),
+        env::var_os("NO_COLOR").as_deref(),
+    )
+}
+
+fn resolve(choice: ColorChoice, is_terminal: bool, no_color: Option<&OsStr>) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => is_terminal && no_color.is_none_or(|v| v.is_empty()),
+    }
+}
+
+pub fn paint(text: &str, color: Color) -> String {
+    format!("\x1B[{}m{text}\x1B[0m", color.code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paint_wraps_text() {
+        assert_eq!(paint("> 05", Color::Yellow), "\x1B[33m> 05\x1B[0m");
+        assert_eq!(paint("", Color::Green), "\x1B[32m\x1B[0m");
+    }
+
+    #[test]
+    fn no_color_disables_auto() {
+        assert!(resolve(ColorChoice::Auto, true, None));
+        assert!(resolve(ColorChoice::Auto, true, Some(OsStr::new(""))));
+        assert!(!resolve(ColorChoice::Auto, true, Some(OsStr::new("1"))));
+    }
+
+    #[test]
+    fn color_choice_resolution() {
+        assert!(!resolve(ColorChoice::Auto, false, None));
+
+        for (is_terminal, no_color) in [(true, None), (false, Some(OsStr::new("1")))] {
+            assert!(resolve(ColorChoice::Always, is_terminal, no_color));
+            assert!(!resolve(ColorChoice::Never, is_terminal, no_color));
+        }
+    }
+}
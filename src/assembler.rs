@@ -1,62 +1,165 @@
 use std::collections::HashMap;
 
-use crate::parse::{Address, Instruction, ParseInfo};
+use crate::parse::{self, Address, Instruction, ParseInfo};
+use crate::LNCError;
 
-pub fn assemble(parse_info: &ParseInfo) -> Result<[usize; 100], String> {
-    if parse_info.instructions.len() >= 100 {
-        return Err(format!(
-            "Too many instructions: {} > 100",
-            parse_info.instructions.len()
-        ));
+// Places each `var` in the free memory after the program, initialised to 0,
+// followed by a cell holding each value used by `ldi`, `addi` or `subi`.
+pub fn allocate_vars(parse_info: &mut ParseInfo, cells: usize) -> Result<(), LNCError> {
+    for (name, &line) in parse_info.vars.iter().zip(&parse_info.var_lines) {
+        let addr = parse_info.instructions.len();
+
+        if addr >= cells {
+            return Err(LNCError::Assemble {
+                msg: format!("No room for variable '{name}': memory is full"),
+            });
+        }
+
+        parse_info.instructions.push(Instruction::Data(0));
+        parse_info.lines.push(line);
+        parse_info.label_map.insert(name.to_owned(), addr);
+    }
+
+    for (&val, &line) in parse_info.consts.iter().zip(&parse_info.const_lines) {
+        let addr = parse_info.instructions.len();
+
+        if addr >= cells {
+            return Err(LNCError::Assemble {
+                msg: format!(
+                    "No room for the constant {val} (used on line {line}): memory is full"
+                ),
+            });
+        }
+
+        parse_info.instructions.push(Instruction::Data(val));
+        parse_info.lines.push(line);
+        parse_info.label_map.insert(parse::const_label(val), addr);
+    }
+
+    Ok(())
+}
+
+// Assembles for a memory of `N` cells, see `MemSize`. This is the second
+// pass: parsing has already put every label in `label_map`, so an operand can
+// name a label that is only defined further down.
+pub fn assemble<const N: usize>(parse_info: &ParseInfo) -> Result<[usize; N], LNCError> {
+    // instructions fill memory from address 0, so the first one that doesn't
+    // fit is the one at address N
+    if parse_info.instructions.len() > N {
+        let location = match parse_info.lines.get(N) {
+            Some(line) => format!("the instruction on line {line}"),
+            None => format!("instruction {}", N + 1),
+        };
+
+        return Err(LNCError::Assemble {
+            msg: format!(
+                "Program too large: {location} would be at address {N}, past the end of memory ({} cells needed, {N} available)",
+                parse_info.instructions.len()
+            ),
+        });
     }
 
-    let mut mem = [0; 100];
+    let mut mem = [0; N];
     let mut errors = vec![];
 
     for (paddr, ins) in parse_info.instructions.iter().enumerate() {
-        match get_code(parse_info, ins) {
+        match get_code(parse_info, paddr, ins, N) {
             Ok(code) => mem[paddr] = code,
-            Err(e) => errors.push(e),
+            Err(msg) => errors.push(LNCError::Assemble { msg }),
         }
     }
 
     if errors.is_empty() {
         Ok(mem)
     } else {
-        Err(errors.join("\n"))
+        Err(LNCError::from_vec(errors))
     }
 }
 
-fn get_code(parse_info: &ParseInfo, ins: &Instruction) -> Result<usize, String> {
+/// The machine code of a single classic (100-cell) instruction. Only numeric
+/// addresses can be encoded, since there are no labels to resolve.
+pub fn encode(ins: &Instruction) -> Result<usize, String> {
+    get_code(&ParseInfo::new(), 0, ins, 100)
+}
+
+// Opcodes are the first digit of a word, followed by the address.
+fn get_code(
+    parse_info: &ParseInfo,
+    paddr: usize,
+    ins: &Instruction,
+    cells: usize,
+) -> Result<usize, String> {
+    let label_map = &parse_info.label_map;
+    let resolve = |addr| resolve_addr(addr, label_map, paddr, cells);
+
     let code = match ins {
-        Instruction::Load(addr) => 500 + resolve_addr(addr, &parse_info.label_map)?,
-        Instruction::Store(addr) => 300 + resolve_addr(addr, &parse_info.label_map)?,
-        Instruction::Add(addr) => 100 + resolve_addr(addr, &parse_info.label_map)?,
-        Instruction::Subtract(addr) => 200 + resolve_addr(addr, &parse_info.label_map)?,
-        Instruction::Input => 901,
-        Instruction::Output => 902,
+        Instruction::Load(addr) => 5 * cells + resolve(addr)?,
+        Instruction::Store(addr) => 3 * cells + resolve(addr)?,
+        Instruction::Add(addr) => cells + resolve(addr)?,
+        Instruction::Subtract(addr) => 2 * cells + resolve(addr)?,
+        Instruction::Input => 9 * cells + 1,
+        Instruction::Output => 9 * cells + 2,
+        Instruction::InputDevice(device) => 9 * cells + 1 + device * 10,
+        Instruction::OutputDevice(device) => 9 * cells + 2 + device * 10,
         Instruction::Halt => 0,
-        Instruction::BranchZero(addr) => 700 + resolve_addr(addr, &parse_info.label_map)?,
-        Instruction::BranchPositive(addr) => 800 + resolve_addr(addr, &parse_info.label_map)?,
-        Instruction::BranchAlways(addr) => 600 + resolve_addr(addr, &parse_info.label_map)?,
+        Instruction::Nop => 9 * cells,
+        Instruction::BranchZero(addr) => 7 * cells + resolve(addr)?,
+        Instruction::BranchPositive(addr) => 8 * cells + resolve(addr)?,
+        Instruction::BranchAlways(addr) => 6 * cells + resolve(addr)?,
         Instruction::Data(data) => *data,
     };
 
     Ok(code)
 }
 
-fn resolve_addr(addr: &Address, label_map: &HashMap<String, usize>) -> Result<usize, String> {
+fn resolve_addr(
+    addr: &Address,
+    label_map: &HashMap<String, usize>,
+    paddr: usize,
+    cells: usize,
+) -> Result<usize, String> {
     match addr {
-        Address::Symbolic(label) => resolve_symb_addr(label, label_map),
+        Address::Symbolic(label) => resolve_symb_addr(label, label_map, paddr),
         Address::Numeric(n) => Ok(*n),
+        Address::Offset(label, offset) => {
+            let base = resolve_symb_addr(label, label_map, paddr)?;
+
+            match base.checked_add(*offset) {
+                Some(addr) if addr < cells => Ok(addr),
+                sum => Err(format!(
+                    "Address {label}+{offset} is out of range: {} > {} (used by instruction at address {paddr})",
+                    sum.map_or(format!("{base}+{offset}"), |addr| addr.to_string()),
+                    cells - 1
+                )),
+            }
+        }
+        Address::Relative(offset) => {
+            let addr = paddr as isize + offset;
+
+            if !(0..cells as isize).contains(&addr) {
+                return Err(format!(
+                    "Address .{offset:+} is out of range: {addr} is not 0-{} (used by instruction at address {paddr})",
+                    cells - 1
+                ));
+            }
+
+            Ok(addr as usize)
+        }
     }
 }
 
-fn resolve_symb_addr(label: &str, label_map: &HashMap<String, usize>) -> Result<usize, String> {
+fn resolve_symb_addr(
+    label: &str,
+    label_map: &HashMap<String, usize>,
+    paddr: usize,
+) -> Result<usize, String> {
     if let Some(addr) = label_map.get(label) {
         Ok(*addr)
     } else {
-        Err(format!("Label '{}' is not defined", label))
+        Err(format!(
+            "Label '{}' is not defined (used by instruction at address {})",
+            label, paddr
+        ))
     }
 }
 
@@ -67,8 +170,8 @@ mod tests {
 
     fn single(source: &str) -> usize {
         let tokens = lex::tokenize(source).unwrap();
-        let parse_info = parse::parse(&tokens).unwrap();
-        let mem = assemble(&parse_info).unwrap();
+        let parse_info = parse::parse(&tokens, 100).unwrap();
+        let mem: [usize; 100] = assemble(&parse_info).unwrap();
         mem[0]
     }
 
@@ -88,10 +191,265 @@ mod tests {
         assert_eq!(single("inp"), 901);
         assert_eq!(single("out"), 902);
         assert_eq!(single("hlt"), 000);
+        assert_eq!(single("nop"), 900);
+    }
+
+    #[test]
+    fn undefined_label_reports_address() {
+        let tokens = lex::tokenize("inp\nout\nlda nowhere\nhlt").unwrap();
+        let parse_info = parse::parse(&tokens, 100).unwrap();
+
+        assert_eq!(
+            assemble::<100>(&parse_info).unwrap_err().to_string(),
+            "Label 'nowhere' is not defined (used by instruction at address 2)"
+        );
+    }
+
+    fn assemble_src(source: &str) -> Result<(ParseInfo, [usize; 100]), LNCError> {
+        let tokens = lex::tokenize(source).unwrap();
+        let mut parse_info = parse::parse(&tokens, 100).unwrap();
+        allocate_vars(&mut parse_info, 100)?;
+        let mem = assemble(&parse_info)?;
+        Ok((parse_info, mem))
+    }
+
+    #[test]
+    fn resolves_forward_references() {
+        let (_, mem) = assemble_src(
+            "
+            lda first
+            add table+1
+            sto result
+            brz done
+            call show
+            done: hlt
+            show: out
+            ret
+            first: dat 5
+            table: dat 1, 2
+            result var",
+        )
+        .unwrap();
+
+        // call show expands to sto call.acc, lda .+4, sto show.ret, lda call.acc,
+        // bra show, dat <bra 10>, and call.acc is declared before result
+        assert_eq!(
+            mem[..18],
+            [513, 115, 317, 710, 316, 509, 312, 516, 611, 610, 0, 902, 0, 5, 1, 2, 0, 0]
+        );
+        assert_eq!(
+            assemble_src("lda later\nhlt\nlaterr: dat 1")
+                .unwrap_err()
+                .to_string(),
+            "Label 'later' is not defined (used by instruction at address 0)"
+        );
+    }
+
+    #[test]
+    fn allocates_vars_after_program() {
+        let (info, mem) = assemble_src(
+            "
+            count var
+            inp
+            sto count
+            lda total
+            total var
+            hlt
+            one: dat 1
+            other var",
+        )
+        .unwrap();
+
+        assert_eq!(info.label_map.get("count"), Some(&5));
+        assert_eq!(info.label_map.get("total"), Some(&6));
+        assert_eq!(info.label_map.get("other"), Some(&7));
+        assert_eq!(mem[..8], [901, 305, 506, 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn allocates_constants_after_vars() {
+        let (info, mem) = assemble_src(
+            "
+            inp
+            addi 10
+            subi 1
+            sto x
+            ldi 10
+            hlt
+            x var",
+        )
+        .unwrap();
+
+        assert_eq!(info.label_map.get("x"), Some(&6));
+        assert_eq!(info.label_map.get("const.10"), Some(&7));
+        assert_eq!(info.label_map.get("const.1"), Some(&8));
+        assert_eq!(mem[..9], [901, 107, 208, 306, 507, 0, 0, 10, 1]);
+        assert_eq!(info.lines[7..], [3, 4]);
+    }
+
+    #[test]
+    fn constants_need_room() {
+        let program = "dat 0\n".repeat(97);
+
+        assert!(assemble_src(&format!("{program}ldi 1\naddi 1")).is_ok());
+        assert_eq!(
+            assemble_src(&format!("{program}ldi 1\naddi 2"))
+                .unwrap_err()
+                .to_string(),
+            "No room for the constant 2 (used on line 99): memory is full"
+        );
+    }
+
+    #[test]
+    fn vars_need_room() {
+        let program = "dat 0\n".repeat(97);
+
+        assert!(assemble_src(&format!("{program}a var\nb var\nc var")).is_ok());
+        assert!(assemble_src(&format!("{program}a var\nb var\nc var\nd var")).is_err());
+    }
+
+    #[test]
+    fn program_fills_memory() {
+        let (_, mem) = assemble_src(&format!("{}dat 7", "nop\n".repeat(99))).unwrap();
+        assert_eq!(mem[98..], [900, 7]);
+
+        assert_eq!(
+            assemble_src(&"nop\n".repeat(101)).unwrap_err().to_string(),
+            "Program too large: the instruction on line 101 would be at address 100, \
+             past the end of memory (101 cells needed, 100 available)"
+        );
+
+        // a dat block that runs past the end is named by its own line
+        let source = format!("{}dat 1, 2, 3\nhlt", "nop\n".repeat(98));
+        assert_eq!(
+            assemble_src(&source).unwrap_err().to_string(),
+            "Program too large: the instruction on line 99 would be at address 100, \
+             past the end of memory (102 cells needed, 100 available)"
+        );
+    }
+
+    #[test]
+    fn assemble_extended() {
+        let tokens = lex::tokenize("inp\nsto total\nout 3\nnop\nbrp .-3\nhlt\ntotal var").unwrap();
+        let mut parse_info = parse::parse(&tokens, 1000).unwrap();
+        allocate_vars(&mut parse_info, 1000).unwrap();
+
+        let mem: [usize; 1000] = assemble(&parse_info).unwrap();
+        assert_eq!(mem[..7], [9001, 3006, 9032, 9000, 8001, 0, 0]);
+
+        let tokens = lex::tokenize(&"nop\n".repeat(1001)).unwrap();
+        let parse_info = parse::parse(&tokens, 1000).unwrap();
+        assert!(assemble::<1000>(&parse_info).is_err());
+    }
+
+    #[test]
+    fn assemble_with_device() {
+        assert_eq!(single("inp 0"), 901);
+        assert_eq!(single("out 0"), 902);
+        assert_eq!(single("inp 4"), 941);
+        assert_eq!(single("out 9"), 992);
+    }
+
+    #[test]
+    fn assemble_offset() {
+        let (_, mem) = assemble_src("lda table+2\nhlt\ntable: dat 1, 2, 3").unwrap();
+        assert_eq!(mem[..5], [504, 0, 1, 2, 3]);
+
+        assert_eq!(single("lda 10+5"), 515);
+        assert_eq!(
+            assemble_src("lda end+1\nend: hlt").unwrap().1,
+            assemble_src("lda 2\nend: hlt").unwrap().1
+        );
+        assert!(assemble_src("lda end+98\nend: hlt").is_ok());
+        assert!(assemble_src("lda end+99\nend: hlt").is_err());
+        assert!(assemble_src("lda end+1").is_err());
+        assert_eq!(
+            assemble_src("nop\nx: dat 0\nlda x+18446744073709551615")
+                .unwrap_err()
+                .to_string(),
+            "Address x+18446744073709551615 is out of range: 1+18446744073709551615 > 99 \
+             (used by instruction at address 2)"
+        );
+    }
+
+    #[test]
+    fn assemble_relative() {
+        let (_, mem) = assemble_src("inp\nbrz .+2\nbra .-2\nhlt").unwrap();
+        assert_eq!(mem[..4], [901, 703, 600, 0]);
+
+        assert_eq!(single("bra ."), 600);
+        assert_eq!(assemble_src("dat 1, 2, 3\nbra .-3").unwrap().1[3], 600);
+        assert!(assemble_src("brp .+99").is_ok());
+        assert!(assemble_src("nop\nbrp .+99").is_err());
+        assert_eq!(
+            assemble_src("nop\nbra .-2").unwrap_err().to_string(),
+            "Address .-2 is out of range: -1 is not 0-99 (used by instruction at address 1)"
+        );
+    }
+
+    #[test]
+    fn encode_single_instructions() {
+        assert_eq!(encode(&Instruction::Load(Address::Numeric(42))), Ok(542));
+        assert_eq!(encode(&Instruction::OutputDevice(3)), Ok(932));
+        assert_eq!(encode(&Instruction::Data(7)), Ok(7));
+        assert!(encode(&Instruction::BranchAlways(Address::Symbolic("x".into()))).is_err());
     }
 
     #[test]
     fn assemble_data() {
         assert_eq!(single("dat 123"), 123);
+        assert_eq!(single("dat 'A'"), 65);
+    }
+
+    #[test]
+    fn expands_call_and_ret() {
+        let source = "
+            inp
+            call double
+            out
+            hlt
+            double: sto tmp
+            add tmp
+            ret
+            tmp: dat 0";
+        let mut parse_info = parse::parse(&lex::tokenize(source).unwrap(), 100).unwrap();
+        allocate_vars(&mut parse_info, 100).unwrap();
+        let mem: [usize; 100] = assemble(&parse_info).unwrap();
+
+        assert_eq!(
+            mem[..14],
+            [
+                901, // inp
+                313, // sto call.acc
+                506, // lda .+4
+                311, // sto double.ret
+                513, // lda call.acc
+                609, // bra double
+                607, // dat: bra 7, just after the call
+                902, 0, 312, 112, 0, // ret, overwritten by each call
+                0, // tmp
+                0, // call.acc
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_return_to_each_caller() {
+        let run = crate::testing::run_source(
+            "
+            inp
+            call double
+            out
+            call double
+            out
+            hlt
+            double: sto tmp
+            add tmp
+            ret
+            tmp: dat 0",
+            &[21],
+        );
+
+        assert_eq!(run.outputs, vec![42, 84]);
     }
 }
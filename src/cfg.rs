@@ -0,0 +1,154 @@
+// Builds a Graphviz control-flow graph of an assembled program. Each node is a
+// basic block, a run of instructions that is only entered at its first
+// instruction and only left after its last one, and each edge is a way control
+// can pass from one block to another.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use crate::disasm;
+use crate::parse::{Instruction, ParseInfo};
+
+fn ends_block(ins: &Instruction) -> bool {
+    matches!(
+        ins,
+        Instruction::Halt
+            | Instruction::BranchZero(_)
+            | Instruction::BranchPositive(_)
+            | Instruction::BranchAlways(_)
+    )
+}
+
+pub fn to_dot(parse_info: &ParseInfo, mem: &[usize; 100]) -> String {
+    let instructions = &parse_info.instructions;
+    let is_code = |addr: usize| {
+        addr < instructions.len() && !matches!(instructions[addr], Instruction::Data(_))
+    };
+    // branch operands are only known once assembled
+    let target = |addr: usize| mem[addr] % 100;
+
+    let mut leaders = BTreeSet::new();
+    for (addr, ins) in instructions.iter().enumerate() {
+        if !is_code(addr) {
+            continue;
+        }
+        if addr == 0 || !is_code(addr - 1) || ends_block(&instructions[addr - 1]) {
+            leaders.insert(addr);
+        }
+        if ends_block(ins) && *ins != Instruction::Halt && is_code(target(addr)) {
+            leaders.insert(target(addr));
+        }
+    }
+
+    let mut labels: HashMap<usize, Vec<&str>> = HashMap::new();
+    for (name, &addr) in &parse_info.label_map {
+        labels.entry(addr).or_default().push(name);
+    }
+
+    let mut nodes = String::new();
+    let mut edges = String::new();
+    // cells that are branched or fallen into without being code
+    let mut outside = BTreeSet::new();
+
+    for &start in &leaders {
+        let mut end = start;
+        while !ends_block(&instructions[end]) && is_code(end + 1) && !leaders.contains(&(end + 1)) {
+            end += 1;
+        }
+
+        let mut lines = vec![];
+        for (addr, &code) in mem.iter().enumerate().take(end + 1).skip(start) {
+            if let Some(names) = labels.get_mut(&addr) {
+                names.sort();
+                lines.extend(names.iter().map(|name| format!("{name}:")));
+            }
+            lines.push(format!("{addr:02}  {}", disasm::mnemonic(code)));
+        }
+        let _ = writeln!(nodes, "    b{start} [label=\"{}\\l\"];", lines.join("\\l"));
+
+        let next = end + 1;
+        let exits = match instructions[end] {
+            Instruction::Halt => vec![],
+            Instruction::BranchAlways(_) => vec![(target(end), None)],
+            Instruction::BranchZero(_) => {
+                vec![(target(end), Some("zero")), (next, Some("not zero"))]
+            }
+            Instruction::BranchPositive(_) => {
+                vec![(target(end), Some("positive")), (next, Some("negative"))]
+            }
+            _ => vec![(next, None)],
+        };
+
+        for (to, label) in exits {
+            let to_node = if is_code(to) {
+                format!("b{to}")
+            } else {
+                outside.insert(to);
+                format!("x{to}")
+            };
+            let attrs = label.map_or(String::new(), |l| format!(" [label=\"{l}\"]"));
+            let _ = writeln!(edges, "    b{start} -> {to_node}{attrs};");
+        }
+    }
+
+    for addr in outside {
+        let label = if addr >= mem.len() {
+            "end of memory".to_owned()
+        } else if addr < instructions.len() {
+            format!("{addr:02}  dat {}", mem[addr])
+        } else {
+            format!("{addr:02}  past the end of the program")
+        };
+        let _ = writeln!(nodes, "    x{addr} [label=\"{label}\", shape=plain];");
+    }
+
+    format!("digraph cfg {{\n    node [shape=box, fontname=\"monospace\"];\n{nodes}{edges}}}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(source: &str) -> String {
+        let program = crate::make_program(source).unwrap();
+        to_dot(&program.parse_info, &program.mem)
+    }
+
+    #[test]
+    fn graph_of_a_loop() {
+        let source = "
+            inp
+            loop: out
+            sub one
+            brp loop
+            hlt
+            one: dat 1";
+
+        assert_eq!(
+            dot(source),
+            r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    b0 [label="00  inp\l"];
+    b1 [label="loop:\l01  out\l02  sub 05\l03  brp 01\l"];
+    b4 [label="04  hlt\l"];
+    b0 -> b1;
+    b1 -> b1 [label="positive"];
+    b1 -> b4 [label="negative"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn edges_out_of_the_program() {
+        let graph = dot("brz 5\nbra data\ndata: dat 7");
+
+        assert!(graph.contains("    b0 -> x5 [label=\"zero\"];\n"));
+        assert!(graph.contains("    b0 -> b1 [label=\"not zero\"];\n"));
+        assert!(graph.contains("    b1 -> x2;\n"));
+        assert!(graph.contains("    x2 [label=\"02  dat 7\", shape=plain];\n"));
+        assert!(
+            graph.contains("    x5 [label=\"05  past the end of the program\", shape=plain];\n")
+        );
+    }
+}
@@ -1,67 +1,277 @@
+use tabled::settings::style::HorizontalLine;
 use tabled::{builder::Builder, settings::Style, Table, Tabled};
 
 use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use std::{fmt, io, io::Write};
 
-use crate::interpreter::{Input, Interpreter, InterpreterState, LNCInput, Log, Output};
-use crate::vec_io::{QueueInput, StackOutput};
-use crate::LNCTest;
+pub use crate::ansi::ColorChoice;
+use crate::ansi::{self, Color};
+use crate::disasm;
+use crate::interpreter::{
+    BrpMode, HaltReason, Input, Interpreter, InterpreterState, LNCInput, Log, MemSize, Output,
+};
+use crate::parse::{self, Address, Instruction, ParseInfo};
+use crate::trace::TraceLogger;
+use crate::vec_io::{
+    format_value, BufferedOutput, PrefilledInput, QueueInput, StackOutput, EMPTY_QUEUE,
+};
+use crate::{LNCError, LNCProgram, LNCTest};
+
+/// Maximum number of instructions a program may execute before it is assumed
+/// to be stuck in an infinite loop.
+const MAX_STEPS: usize = 100_000;
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutFormat {
+    /// [1, 2, 3]
+    #[default]
+    List,
+    /// one value per line
+    Lines,
+    /// 1,2,3
+    Comma,
+    /// [1,2,3]
+    Json,
+}
+
+/// How tables are drawn: with box-drawing characters, or in plain ASCII for
+/// terminals and log files that render those poorly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TableStyle {
+    #[default]
+    Sharp,
+    Ascii,
+}
+
+impl TableStyle {
+    fn render(self, mut table: Table) -> String {
+        match self {
+            TableStyle::Sharp => table.with(Style::sharp()).to_string(),
+            // laid out like the sharp style: only the header is underlined
+            TableStyle::Ascii => table
+                .with(
+                    Style::ascii()
+                        .remove_horizontal()
+                        .horizontals([(1, HorizontalLine::full('-', '+', '+', '+'))]),
+                )
+                .to_string(),
+        }
+    }
+
+    fn vertical(self) -> char {
+        match self {
+            TableStyle::Sharp => '│',
+            TableStyle::Ascii => '|',
+        }
+    }
+}
 
 #[derive(Default)]
+pub struct RunOptions {
+    pub buffered_output: bool,
+    pub signed_output: bool,
+    pub out_format: OutFormat,
+    pub trace: Option<PathBuf>,
+    pub pause_on_output: bool,
+    pub brp_mode: BrpMode,
+    // defaults to MAX_STEPS
+    pub max_steps: Option<usize>,
+    // only has an effect when running from source
+    pub protect_code: bool,
+    pub check_uninit: bool,
+    pub check_branches: bool,
+    // print a plain-English description of each instruction instead of the log
+    pub explain: bool,
+    // don't log each instruction, which is much faster for long runs
+    pub quiet: bool,
+    // shown instead of DEFAULT_PROMPT when asking for an input
+    pub prompt: Option<String>,
+    // only has an effect when running from source
+    pub mem_size: MemSize,
+    // fed to the program before asking for any input interactively
+    pub inputs: Vec<usize>,
+    pub no_summary: bool,
+    // print the most executed addresses after the run
+    pub profile: bool,
+    // log every write to these addresses
+    pub watch_addrs: Vec<usize>,
+    // (addr, value) pairs written into memory before the program starts, only
+    // has an effect when running from source
+    pub data: Vec<(usize, usize)>,
+    // add how long the run took to the summary
+    pub time: bool,
+    // `make_strict_program` rather than `make_program`, only for classic memory
+    pub strict: bool,
+}
+
+const DEFAULT_PROMPT: &str = "Enter input value: ";
+// rows in the `--profile` table
+const PROFILE_ROWS: usize = 10;
+// invalid values in a row before `CLIInput` gives up, e.g. on piped input
+const MAX_INPUT_ATTEMPTS: usize = 5;
+
 struct CLIInput {
     history: Vec<usize>,
+    prompt: String,
+    // inputs must be below this
+    limit: usize,
+    // time spent waiting for inputs to be entered
+    waited: Duration,
 }
 
-impl Input for CLIInput {
-    fn take(&mut self) -> Result<LNCInput, String> {
-        print!("Enter input value: ");
-        let _ = io::stdout().flush();
+impl Default for CLIInput {
+    fn default() -> Self {
+        Self::with_prompt(DEFAULT_PROMPT)
+    }
+}
 
-        let mut input = String::new();
-        if let Err(e) = io::stdin().read_line(&mut input) {
-            return Err(format!("Error: {e:?}"));
+impl CLIInput {
+    fn with_prompt(prompt: &str) -> Self {
+        Self {
+            history: vec![],
+            prompt: prompt.into(),
+            limit: 1000,
+            waited: Duration::ZERO,
         }
+    }
 
-        let num = match input.trim().parse::<usize>() {
-            Ok(n) => n,
-            Err(e) => return Err(format!("Error with input \"{}\": {e:?}", input.trim())),
-        };
+    // Prompts until a valid value is entered: an invalid one is reported and
+    // asked for again. Only EOF or too many invalid values end the run.
+    fn read_from(
+        &mut self,
+        reader: &mut impl io::BufRead,
+        writer: &mut impl Write,
+    ) -> Result<LNCInput, String> {
+        for _ in 0..MAX_INPUT_ATTEMPTS {
+            let _ = write!(writer, "{}", self.prompt);
+            let _ = writer.flush();
+
+            let mut input = String::new();
+            match reader.read_line(&mut input) {
+                Ok(0) => return Err("Error: no more input (end of file)".into()),
+                Ok(_) => (),
+                Err(e) => return Err(format!("Error: {e:?}")),
+            }
+
+            match self.parse_value(input.trim()) {
+                Ok(lnc_num) => return Ok(lnc_num),
+                Err(e) => {
+                    let _ = writeln!(writer, "{e}, try again");
+                }
+            }
+        }
 
-        let maybe_lnc_num = LNCInput::new(num);
+        Err(format!(
+            "Error: gave up after {MAX_INPUT_ATTEMPTS} invalid inputs"
+        ))
+    }
+
+    fn parse_value(&mut self, input: &str) -> Result<LNCInput, String> {
+        let num = input
+            .parse::<usize>()
+            .map_err(|e| format!("Error with input \"{input}\": {e}"))?;
 
-        match maybe_lnc_num {
+        match LNCInput::with_limit(num, self.limit) {
             Some(lnc_num) => {
                 self.history.push(num);
                 Ok(lnc_num)
             }
-            None => Err("Error: input too large".into()),
+            None => Err(format!(
+                "Error: input {num} is too large (must be below {})",
+                self.limit
+            )),
         }
     }
 }
 
+impl Input for CLIInput {
+    fn take(&mut self) -> Result<LNCInput, String> {
+        let start = Instant::now();
+        let res = self.read_from(&mut io::stdin().lock(), &mut io::stdout());
+        self.waited += start.elapsed();
+        res
+    }
+}
+
 #[derive(Default)]
 struct CLIOutput {
     history: Vec<usize>,
+    signed: bool,
 }
 
 impl Output for CLIOutput {
     fn send(&mut self, val: usize) {
         self.history.push(val);
-        println!("Output: {val}");
+        println!("Output: {}", format_value(val, self.signed));
+    }
+}
+
+// Waits for the user to press enter after every value sent to `inner`.
+struct PausingOutput<'a> {
+    inner: &'a mut dyn Output,
+}
+
+impl Output for PausingOutput<'_> {
+    fn send(&mut self, val: usize) {
+        self.inner.send(val);
+
+        print!("(press enter to continue) ");
+        let _ = io::stdout().flush();
+        let _ = io::stdin().read_line(&mut String::new());
     }
 }
 
-struct CLILogger;
+// With `explain` set, the plain-English explanations are printed instead of
+// the terse log messages. With `quiet`, the log messages aren't printed at all.
+// Watches and warnings are always printed.
+struct CLILogger<W: Write = io::Stdout> {
+    explain: bool,
+    quiet: bool,
+    w: W,
+}
+
+impl Default for CLILogger {
+    fn default() -> Self {
+        Self {
+            explain: false,
+            quiet: false,
+            w: io::stdout(),
+        }
+    }
+}
 
-impl Log for CLILogger {
+impl<W: Write> Log for CLILogger<W> {
     fn log(&mut self, msg: String) {
-        println!("{msg}");
+        let _ = writeln!(self.w, "{msg}");
+    }
+
+    fn enabled(&self) -> bool {
+        !self.explain && !self.quiet
+    }
+
+    fn explain(&mut self, msg: String) {
+        let _ = writeln!(self.w, "{msg}");
+    }
+
+    fn watch(&mut self, msg: String) {
+        let _ = writeln!(self.w, "{msg}");
+    }
+
+    fn warn(&mut self, msg: String) {
+        let _ = writeln!(self.w, "{msg}");
     }
 }
 
 enum TestResult {
     Passed,
     Failed(String),
+    TimedOut(usize),
+    Skipped,
 }
 
 impl fmt::Display for TestResult {
@@ -69,6 +279,11 @@ impl fmt::Display for TestResult {
         match self {
             Self::Passed => write!(f, "ok"),
             Self::Failed(msg) => write!(f, "failed: {msg}"),
+            Self::TimedOut(max_steps) => write!(
+                f,
+                "failed: timed out after {max_steps} instructions (possible infinite loop)"
+            ),
+            Self::Skipped => write!(f, "skipped"),
         }
     }
 }
@@ -88,7 +303,11 @@ impl LNCTestInfo {
         Self {
             name: test.name.to_owned(),
             input: format!("{:?}", test.inputs),
-            expected_output: format!("{:?}", test.outputs),
+            expected_output: if test.expect_error {
+                "error".into()
+            } else {
+                format!("{:?}", test.outputs)
+            },
             actual_output: format!("{actual_output:?}"),
             ins_count,
             result,
@@ -96,241 +315,2642 @@ impl LNCTestInfo {
     }
 }
 
-pub fn run(source: &str) -> Result<(), String> {
-    let mem = crate::make_program(source)?.mem;
+pub fn run(source: &str, options: &RunOptions) -> Result<Vec<usize>, String> {
+    match options.mem_size {
+        MemSize::Classic => {
+            let program = if options.strict {
+                crate::make_strict_program(source)?
+            } else {
+                crate::make_program(source)?
+            };
+            print_warnings(&program.warnings);
 
-    let mut input = CLIInput::default();
-    let mut output = CLIOutput::default();
-    let mut logger = CLILogger;
+            run_program(&program.parse_info, program.mem, options)
+        }
+        MemSize::Extended => {
+            let (parse_info, mem) = crate::assemble_source::<1000>(source)?;
+
+            run_program(&parse_info, mem, options)
+        }
+    }
+}
+
+fn run_program<const N: usize>(
+    parse_info: &ParseInfo,
+    mut mem: [usize; N],
+    options: &RunOptions,
+) -> Result<Vec<usize>, String> {
+    let code = code_ranges(parse_info);
+    print_warnings(&apply_data(&mut mem, &options.data, &code)?);
+
+    let protected = if options.protect_code { code } else { vec![] };
+
+    let len = parse_info.instructions.len();
+    Ok(run_image(mem, options, len, &protected)?.to_vec())
+}
 
-    let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
-    let mut ins_count = 0;
+// Parses a `--data` argument, `ADDR=VALUE`.
+pub fn parse_data_arg(arg: &str) -> Result<(usize, usize), String> {
+    let parse = |s: &str| s.trim().parse::<usize>().ok();
 
-    while !interpreter.is_halted() {
-        interpreter.step()?;
-        ins_count += 1;
+    match arg.split_once('=') {
+        Some((addr, val)) => match (parse(addr), parse(val)) {
+            (Some(addr), Some(val)) => Ok((addr, val)),
+            _ => Err(format!("invalid data \"{arg}\": expected ADDR=VALUE")),
+        },
+        None => Err(format!("invalid data \"{arg}\": expected ADDR=VALUE")),
     }
+}
 
-    println!("\n--- summary ---");
-    println!("instruction count: {ins_count}");
-    println!("in:  {:?}", input.history);
-    println!("out: {:?}", output.history);
+// Checks that each (addr, value) fits in a memory of `N` cells.
+fn check_data<const N: usize>(data: &[(usize, usize)]) -> Result<(), String> {
+    for &(addr, val) in data {
+        if addr >= N {
+            return Err(format!(
+                "invalid data {addr}={val}: address must be 0-{}",
+                N - 1
+            ));
+        }
+        if val >= N * 10 {
+            return Err(format!(
+                "invalid data {addr}={val}: value must be 0-{}",
+                N * 10 - 1
+            ));
+        }
+    }
 
     Ok(())
 }
 
-pub fn run_tests(source: &str) -> Result<(), String> {
-    let program = crate::make_program(source)?;
-    let (mem, tests) = (program.mem, program.parse_info.tests);
+// Writes each (addr, value) into memory, returning a warning for each one that
+// overwrites an instruction in `code`.
+fn apply_data<const N: usize>(
+    mem: &mut [usize; N],
+    data: &[(usize, usize)],
+    code: &[Range<usize>],
+) -> Result<Vec<String>, String> {
+    check_data::<N>(data)?;
+    let mut warnings = vec![];
 
-    let mut results = vec![];
+    for &(addr, val) in data {
+        if code.iter().any(|range| range.contains(&addr)) {
+            warnings.push(format!(
+                "warning: data {addr}={val} overwrites the instruction at address {addr}"
+            ));
+        }
+        mem[addr] = val;
+    }
+
+    Ok(warnings)
+}
+
+// Runs of consecutive instructions, leaving `dat` and `var` cells writable.
+fn code_ranges(parse_info: &ParseInfo) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+
+    for (addr, ins) in parse_info.instructions.iter().enumerate() {
+        if matches!(ins, Instruction::Data(_)) {
+            continue;
+        }
 
-    for test in tests.iter() {
-        results.push(run_test(mem, test)?);
+        match ranges.last_mut() {
+            Some(range) if range.end == addr => range.end += 1,
+            _ => ranges.push(addr..addr + 1),
+        }
     }
 
-    println!("\n--- test results ---");
-    println!("{}", Table::new(results).with(Style::sharp()));
+    ranges
+}
 
-    Ok(())
+pub fn run_bin(bytes: &[u8], options: &RunOptions) -> Result<Vec<usize>, String> {
+    let program = LNCProgram::from_bytes(bytes)?;
+
+    Ok(run_image(program.mem, options, used_len(&program.mem), &[])?.to_vec())
+}
+
+pub fn run_mem(contents: &str, options: &RunOptions) -> Result<Vec<usize>, String> {
+    let mem = parse_mem(contents)?;
+    Ok(run_image(mem, options, used_len(&mem), &[])?.to_vec())
+}
+
+// Reads up to 100 whitespace-separated cell values, zero-filling the rest.
+fn parse_mem(contents: &str) -> Result<[usize; 100], String> {
+    let mut mem = [0; 100];
+    let values: Vec<&str> = contents.split_whitespace().collect();
+
+    if values.len() > 100 {
+        return Err(format!(
+            "too many values: memory holds 100 cells, found {}",
+            values.len()
+        ));
+    }
+
+    for (addr, value) in values.into_iter().enumerate() {
+        let cell = value
+            .parse::<usize>()
+            .map_err(|_| format!("invalid value \"{value}\" at address {addr}"))?;
+
+        if cell >= 1000 {
+            return Err(format!("value {cell} at address {addr} is too large"));
+        }
+
+        mem[addr] = cell;
+    }
+
+    Ok(mem)
 }
 
-pub fn run_debugger(source: &str) -> Result<(), String> {
+pub fn disasm(source: &str, style: TableStyle) -> Result<(), String> {
     let program = crate::make_program(source)?;
+    print_warnings(&program.warnings);
 
-    let mem = program.mem;
     let addr_to_label: HashMap<usize, String> = program
         .parse_info
         .label_map
-        .into_iter()
-        .map(|(k, v)| (v, k))
+        .iter()
+        .map(|(k, &v)| (v, k.to_owned()))
         .collect();
 
-    let mut input = CLIInput::default();
-    let mut output = CLIOutput::default();
-    let mut logger = CLILogger;
+    let info = &program.parse_info;
+    println!(
+        "{}",
+        make_disasm_table(
+            &program.mem,
+            &addr_to_label,
+            &info.lines,
+            info.instructions.len(),
+            style
+        )
+    );
 
-    let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
-    let mut ins_count = 0;
-    let mut skip_count = 0;
+    Ok(())
+}
 
-    while !interpreter.is_halted() {
-        println!("\n--- ins #{ins_count} ---");
-        let state = interpreter.state();
+// Prints only the graph, without warnings, so it can be piped into `dot`.
+pub fn print_cfg(source: &str) -> Result<(), String> {
+    let program = crate::make_program(source)?;
+    print!("{}", crate::cfg::to_dot(&program.parse_info, &program.mem));
 
-        println!("{}", make_mem_table(&state, &addr_to_label, 15));
-        println!("{}", make_state_table(&state));
+    Ok(())
+}
 
-        if skip_count == 0 {
-            loop {
-                print!(">>> ");
-                let _ = io::stdout().flush();
+pub fn xref(source: &str, style: TableStyle) -> Result<(), String> {
+    let program = crate::make_program(source)?;
+    print_warnings(&program.warnings);
+    println!(
+        "{}",
+        make_xref_table(&program.parse_info, &program.mem, style)
+    );
 
-                let mut input = String::new();
+    Ok(())
+}
 
-                if io::stdin().read_line(&mut input).is_err() {
-                    continue;
-                }
+// One row per label: what it labels, and the address of each instruction that
+// uses it as an operand.
+fn make_xref_table(parse_info: &ParseInfo, mem: &[usize; 100], style: TableStyle) -> String {
+    let mut refs: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (addr, ins) in parse_info.instructions.iter().enumerate() {
+        let operand = match ins {
+            Instruction::Load(a)
+            | Instruction::Store(a)
+            | Instruction::Add(a)
+            | Instruction::Subtract(a)
+            | Instruction::BranchZero(a)
+            | Instruction::BranchPositive(a)
+            | Instruction::BranchAlways(a) => a,
+            _ => continue,
+        };
+        if let Address::Symbolic(name) | Address::Offset(name, _) = operand {
+            refs.entry(name).or_default().push(addr);
+        }
+    }
 
-                let input = input.trim();
+    // the labels `call` and `ret` make up for themselves all contain a `.`
+    let mut symbols: Vec<(&String, usize)> = parse_info
+        .label_map
+        .iter()
+        .filter(|(name, _)| !name.contains('.'))
+        .map(|(name, &addr)| (name, addr))
+        .collect();
+    symbols.sort_by_key(|&(name, addr)| (addr, name));
 
-                if input.is_empty() {
-                    skip_count = 1;
-                    break;
-                }
+    let mut builder = Builder::default();
+    builder.push_record(["label", "addr", "kind", "value", "referenced by"]);
 
-                match input.parse::<usize>() {
-                    Ok(n) => {
-                        skip_count = n.max(1);
-                        break;
-                    }
-                    Err(_) => continue,
-                };
-            }
-        }
+    for (name, addr) in symbols {
+        let (kind, value) = if parse_info.vars.contains(name) {
+            ("var", format!("{:03}", mem[addr]))
+        } else if addr >= parse_info.instructions.len() {
+            ("end", "-".to_owned())
+        } else if let Instruction::Data(_) = parse_info.instructions[addr] {
+            ("dat", format!("{:03}", mem[addr]))
+        } else {
+            ("code", disasm::mnemonic(mem[addr]))
+        };
+
+        let referenced_by = match refs.get(name.as_str()) {
+            Some(addrs) => addrs
+                .iter()
+                .map(|a| format!("{a:02}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => "unreferenced".to_owned(),
+        };
 
-        interpreter.step()?;
-        ins_count += 1;
-        skip_count -= 1;
+        builder.push_record([
+            name.clone(),
+            format!("{addr:02}"),
+            kind.to_owned(),
+            value,
+            referenced_by,
+        ]);
     }
 
-    let mut builder = Builder::default();
-    builder.push_record(["ins_count", "in", "out"]);
-    builder.push_record([
-        ins_count.to_string(),
-        format!("{:?}", input.history),
-        format!("{:?}", output.history),
-    ]);
+    style.render(builder.build())
+}
 
-    let result_table = builder.build().with(Style::sharp()).to_string();
+pub fn disasm_bin(bytes: &[u8], style: TableStyle) -> Result<(), String> {
+    let program = LNCProgram::from_bytes(bytes)?;
+    println!(
+        "{}",
+        make_disasm_table(
+            &program.mem,
+            &HashMap::new(),
+            &[],
+            used_len(&program.mem),
+            style
+        )
+    );
 
-    println!("\n--- summary ---");
-    println!("{result_table}");
+    Ok(())
+}
+
+pub fn disasm_mem(contents: &str, style: TableStyle) -> Result<(), String> {
+    let mem = parse_mem(contents)?;
+    println!(
+        "{}",
+        make_disasm_table(&mem, &HashMap::new(), &[], used_len(&mem), style)
+    );
 
     Ok(())
 }
 
-fn make_mem_table(
-    state: &InterpreterState,
+// Without the source, the program is assumed to end at the last non-zero cell.
+// A trailing hlt can't be told apart from empty memory, so at least one cell
+// is always listed.
+fn used_len(mem: &[usize; 100]) -> usize {
+    mem.iter()
+        .rposition(|&cell| cell != 0)
+        .map_or(1, |last| last + 1)
+}
+
+// Lists the first `len` cells, plus any labelled cells after them. The source
+// line column is left out when `lines` is empty.
+fn make_disasm_table(
+    mem: &[usize; 100],
     addr_to_label: &HashMap<usize, String>,
-    num_lines: usize,
+    lines: &[usize],
+    len: usize,
+    style: TableStyle,
 ) -> String {
+    let len = addr_to_label
+        .keys()
+        .map(|addr| addr + 1)
+        .fold(len, usize::max)
+        .min(100);
+
     let mut builder = Builder::default();
-    builder.push_record(["pc", "addr", "label", "mnemonic", "mem"]);
+    let show_lines = !lines.is_empty();
 
-    let (min, max) = if state.pc < num_lines / 2 {
-        (0, num_lines - 1)
-    } else if state.pc > (99 - num_lines / 2) {
-        (99 - num_lines + 1, 99)
+    let mut header = vec!["addr", "label", "mnemonic", "mem"];
+    if show_lines {
+        header.insert(1, "line");
+    }
+    builder.push_record(header);
+
+    for (addr, &val) in mem.iter().enumerate().take(len) {
+        let label = addr_to_label.get(&addr).map_or("", |l| l.as_str());
+
+        let mut record = vec![
+            format!("{addr:02}"),
+            label.to_owned(),
+            disasm::mnemonic(val),
+            format!("{val:03}"),
+        ];
+        if show_lines {
+            let line = lines.get(addr).map_or(String::new(), |l| l.to_string());
+            record.insert(1, line);
+        }
+        builder.push_record(record);
+    }
+
+    style.render(builder.build())
+}
+
+// Assembles the program without running it, reporting every error and warning.
+// With `strict`, see `make_strict_program`.
+pub fn check(source: &str, strict: bool) -> Result<(), String> {
+    let program = if strict {
+        crate::make_strict_program(source)
     } else {
-        (state.pc - num_lines / 2, state.pc + num_lines / 2)
+        crate::make_program(source)
     };
 
-    for (addr, val) in state
-        .mem
-        .iter()
-        .enumerate()
-        .filter(|(addr, _)| *addr >= min && *addr <= max)
-    {
-        let arrow = if addr == state.pc { ">" } else { "" };
-        let addr_str = format!("{addr:02}");
-        let label = if let Some(l) = addr_to_label.get(&addr) {
-            l
-        } else {
-            ""
-        };
+    match program {
+        Ok(program) => {
+            print_warnings(&program.warnings);
+            println!(
+                "no errors, {}",
+                count_noun(program.warnings.len(), "warning")
+            );
+            Ok(())
+        }
+        Err(e) => Err(format!("{e}\n{}", count_noun(e.errors().len(), "error"))),
+    }
+}
 
-        let first_digit = val / 100;
-        let op = val % 100;
-        let mnemonic = match first_digit {
-            5 => format!("lda {:02}", op),
-            3 => format!("sto {:02}", op),
-            1 => format!("add {:02}", op),
-            2 => format!("sub {:02}", op),
-            9 => match op {
-                01 => "inp".to_owned(),
-                02 => "out".to_owned(),
-                _ => "".to_owned(),
-            },
-            0 => {
-                if op == 0 {
-                    "hlt".to_owned()
-                } else {
-                    "".to_owned()
-                }
+fn count_noun(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{n} {noun}s")
+    }
+}
+
+// With `text`, the program is written as machine code text rather than a
+// binary image.
+pub fn assemble_to(source: &str, path: &Path, text: bool) -> Result<(), String> {
+    let program = crate::make_program(source)?;
+    print_warnings(&program.warnings);
+
+    let contents = if text {
+        program.to_machine_text().into_bytes()
+    } else {
+        program.to_bytes()
+    };
+
+    fs::write(path, contents).map_err(|e| format!("error writing {}: {e}", path.display()))
+}
+
+// `len` is the size of the program: with `check_uninit`, cells past it count as
+// uninitialised until they are stored to, and with `check_branches`, branching
+// past it is warned about.
+fn run_image<const N: usize>(
+    mem: [usize; N],
+    options: &RunOptions,
+    len: usize,
+    protected: &[Range<usize>],
+) -> Result<[usize; N], String> {
+    run_image_to(&mut io::stdout(), mem, options, len, protected)
+}
+
+// Like `run_image`, but writes the summary to `summary` instead of stdout.
+fn run_image_to<const N: usize>(
+    summary: &mut dyn Write,
+    mem: [usize; N],
+    options: &RunOptions,
+    len: usize,
+    protected: &[Range<usize>],
+) -> Result<[usize; N], String> {
+    if let Some(addr) = options.watch_addrs.iter().find(|&&addr| addr >= N) {
+        return Err(format!("invalid --watch-addr {addr}: must be 0-{}", N - 1));
+    }
+    check_data::<N>(&options.data)?;
+
+    let mut input = PrefilledInput {
+        queue: QueueInput::with_limit(&options.inputs, N * 10)?,
+        fallback: CLIInput {
+            limit: N * 10,
+            ..CLIInput::with_prompt(options.prompt.as_deref().unwrap_or(DEFAULT_PROMPT))
+        },
+    };
+    let mut buffered_output = BufferedOutput::new(options.signed_output);
+    let mut cli_output = CLIOutput {
+        signed: options.signed_output,
+        ..Default::default()
+    };
+
+    let output: &mut dyn Output = if options.buffered_output {
+        &mut buffered_output
+    } else {
+        &mut cli_output
+    };
+    let mut pausing_output;
+    let output: &mut dyn Output = if options.pause_on_output {
+        pausing_output = PausingOutput { inner: output };
+        &mut pausing_output
+    } else {
+        output
+    };
+
+    let mut trace_logger = match &options.trace {
+        Some(path) => {
+            let file = File::create(path)
+                .map_err(|e| format!("error creating trace file {}: {e}", path.display()))?;
+            let logger = TraceLogger::new(
+                BufWriter::new(file),
+                CLILogger {
+                    explain: options.explain,
+                    quiet: options.quiet,
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| format!("error writing trace file: {e}"))?;
+            Some(logger)
+        }
+        None => None,
+    };
+    let mut cli_logger = CLILogger {
+        explain: options.explain,
+        quiet: options.quiet,
+        ..Default::default()
+    };
+
+    let logger: &mut dyn Log = match &mut trace_logger {
+        Some(logger) => logger,
+        None => &mut cli_logger,
+    };
+
+    let start = Instant::now();
+    let (mem, mut stats, profile) = {
+        let mut interpreter = Interpreter::new(mem, &mut input, output, logger)
+            .with_brp_mode(options.brp_mode)
+            .with_explanations(options.explain);
+        if options.check_uninit {
+            let mut initialised = [false; N];
+            initialised[..len].fill(true);
+            // `--data` cells were checked against N above
+            for &(addr, _) in &options.data {
+                initialised[addr] = true;
             }
-            7 => format!("brz {:02}", op),
-            8 => format!("brp {:02}", op),
-            6 => format!("bra {:02}", op),
-            _ => "".to_owned(),
+            interpreter = interpreter.with_uninit_checks(initialised);
+        }
+        if options.check_branches {
+            interpreter = interpreter.with_branch_checks(len);
+        }
+        for range in protected {
+            interpreter.protect(range.clone());
+        }
+        if options.profile {
+            interpreter = interpreter.with_profiling();
+        }
+        if !options.watch_addrs.is_empty() {
+            interpreter = interpreter.with_watched_addrs(&options.watch_addrs);
+        }
+        let reason = interpreter.run_until_stopped(options.max_steps.unwrap_or(MAX_STEPS));
+        let state = interpreter.state();
+
+        let stats = RunStats {
+            ins_count: interpreter.ins_count(),
+            max_acc: interpreter.max_acc(),
+            overflows: interpreter.overflow_count(),
+            underflows: interpreter.underflow_count(),
+            reason,
+            program_len: len,
+            acc: state.acc,
+            neg_flag: state.neg_flag,
+            pc: state.pc,
+            elapsed: None,
         };
-        let val_str = format!("{:03}", val);
 
-        builder.push_record([arrow, &addr_str, label, &mnemonic, &val_str]);
+        (state.mem, stats, interpreter.profile())
+    };
+
+    if options.time {
+        stats.elapsed = Some(start.elapsed().saturating_sub(input.fallback.waited));
     }
 
-    builder.build().with(Style::sharp()).to_string()
-}
+    buffered_output.flush();
+    if let Some(logger) = trace_logger {
+        logger
+            .finish()
+            .map_err(|e| format!("error writing trace file: {e}"))?;
+    }
 
-fn make_state_table(state: &InterpreterState) -> String {
-    let mut builder = Builder::default();
+    let outputs = if options.buffered_output {
+        buffered_output.history
+    } else {
+        cli_output.history
+    };
 
-    builder.push_record(["pc", "acc", "neg_flag", "halted"]);
-    builder.push_record([
-        state.pc.to_string(),
-        state.acc.to_string(),
-        state.neg_flag.to_string(),
-        state.halted.to_string(),
-    ]);
+    let used = input.queue.provided - input.queue.queue.len();
+    let inputs = [&options.inputs[..used], &input.fallback.history].concat();
 
-    builder.build().with(Style::sharp()).to_string()
-}
+    // the summary is printed even if the program failed, so the inputs and
+    // outputs leading up to the error aren't lost
+    let _ = write_summary(summary, &stats, &inputs, &outputs, options);
+    if options.profile {
+        println!("\n--- profile ---");
+        println!(
+            "{}",
+            make_profile_table(&profile, &mem, TableStyle::default())
+        );
+    }
 
-fn run_test(mem: [usize; 100], test: &LNCTest) -> Result<LNCTestInfo, String> {
-    let mut input = QueueInput::new(&test.inputs)?;
-    let mut output = StackOutput::default();
-    let mut logger = CLILogger;
+    match stats.reason {
+        HaltReason::Halted { .. } => Ok(mem),
+        HaltReason::StepLimit { max_steps } => Err(LNCError::StepLimit { max_steps }.into()),
+        HaltReason::Error(e) => Err(e.into()),
+    }
+}
 
-    let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
-    let mut ins_count = 0;
+struct RunStats {
+    ins_count: usize,
+    max_acc: usize,
+    overflows: usize,
+    underflows: usize,
+    reason: HaltReason,
+    // a `hlt` at or past this is most likely empty memory
+    program_len: usize,
+    // the registers once the program stopped
+    acc: usize,
+    neg_flag: bool,
+    pc: usize,
+    // how long the run took, not counting waiting for inputs, with `--time`
+    elapsed: Option<Duration>,
+}
 
-    while !interpreter.is_halted() {
-        match interpreter.step() {
-            Ok(_) => (),
-            Err(e) => {
-                return Ok(LNCTestInfo::new(
-                    test,
-                    &output.stack,
-                    ins_count,
-                    TestResult::Failed(e),
-                ));
+impl RunStats {
+    fn describe_stop(&self) -> String {
+        match &self.reason {
+            HaltReason::Halted { addr } if *addr >= self.program_len => {
+                format!("hlt at address {addr}, past the end of the program (empty memory?)")
             }
+            HaltReason::Halted { addr } => format!("hlt at address {addr}"),
+            HaltReason::StepLimit { max_steps } => format!("step limit of {max_steps} reached"),
+            HaltReason::Error(_) => "runtime error".into(),
         }
-        ins_count += 1;
     }
+}
 
-    if !input.queue.is_empty() {
-        return Ok(LNCTestInfo::new(
-            test,
-            &output.stack,
-            ins_count,
-            TestResult::Failed(format!("unused inputs: {:?}", input.queue)),
-        ));
+fn write_summary(
+    w: &mut dyn Write,
+    stats: &RunStats,
+    inputs: &[usize],
+    outputs: &[usize],
+    options: &RunOptions,
+) -> io::Result<()> {
+    if options.no_summary {
+        return Ok(());
     }
 
-    if output.stack != test.outputs {
-        return Ok(LNCTestInfo::new(
-            test,
-            &output.stack,
-            ins_count,
-            TestResult::Failed("incorrect outputs".into()),
-        ));
+    writeln!(w, "\n--- summary ---")?;
+    writeln!(w, "stopped by: {}", stats.describe_stop())?;
+    writeln!(w, "instruction count: {}", stats.ins_count)?;
+    writeln!(w, "peak acc: {}", stats.max_acc)?;
+    writeln!(
+        w,
+        "final acc: {}, neg_flag: {}, pc: {}",
+        format_value(stats.acc, options.signed_output),
+        stats.neg_flag,
+        stats.pc
+    )?;
+    writeln!(
+        w,
+        "overflows: {}, underflows: {}",
+        stats.overflows, stats.underflows
+    )?;
+    if let Some(elapsed) = stats.elapsed {
+        writeln!(w, "time: {}", format_timing(stats.ins_count, elapsed))?;
     }
+    writeln!(w, "in:  {inputs:?}")?;
+    let outputs = format_outputs(outputs, options.signed_output, options.out_format);
+    if options.out_format == OutFormat::Lines {
+        writeln!(w, "out:\n{outputs}")
+    } else {
+        writeln!(w, "out: {outputs}")
+    }
+}
 
-    Ok(LNCTestInfo::new(
-        test,
-        &output.stack,
-        ins_count,
-        TestResult::Passed,
-    ))
+// The `PROFILE_ROWS` most executed addresses, most executed first. The
+// mnemonics are of memory once the program stopped.
+fn make_profile_table(profile: &[usize], mem: &[usize], style: TableStyle) -> String {
+    let total: usize = profile.iter().sum();
+    let mut hottest: Vec<(usize, usize)> = profile
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count > 0)
+        .map(|(addr, &count)| (addr, count))
+        .collect();
+    hottest.sort_by_key(|&(addr, count)| (std::cmp::Reverse(count), addr));
+
+    let mut builder = Builder::default();
+    builder.push_record(["addr", "mnemonic", "count", "share"]);
+
+    for (addr, count) in hottest.into_iter().take(PROFILE_ROWS) {
+        // only classic instructions can be disassembled
+        let ins = if mem.len() == 100 {
+            disasm::mnemonic(mem[addr])
+        } else {
+            format!("{:04}", mem[addr])
+        };
+        builder.push_record([
+            format!("{addr:02}"),
+            ins,
+            count.to_string(),
+            format!("{:.1}%", 100.0 * count as f64 / total as f64),
+        ]);
+    }
+
+    style.render(builder.build())
+}
+
+fn format_timing(ins_count: usize, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    let rate = if secs > 0.0 {
+        format!("{:.0}", ins_count as f64 / secs)
+    } else {
+        "-".into()
+    };
+
+    format!("{:.3} ms ({rate} instructions/s)", secs * 1000.0)
+}
+
+pub fn format_outputs(outputs: &[usize], signed: bool, format: OutFormat) -> String {
+    let values: Vec<String> = outputs.iter().map(|&v| format_value(v, signed)).collect();
+
+    match format {
+        OutFormat::List => format!("[{}]", values.join(", ")),
+        OutFormat::Lines => values.join("\n"),
+        OutFormat::Comma => values.join(","),
+        OutFormat::Json => format!("[{}]", values.join(",")),
+    }
+}
+
+// Rows of ten cells. Works for any memory size, padding addresses and values
+// to the widths it needs.
+pub fn format_mem_grid(mem: &[usize]) -> String {
+    let addr_width = (mem.len() - 1).to_string().len();
+    let value_width = mem.len().ilog10() as usize + 1;
+
+    mem.chunks(10)
+        .enumerate()
+        .map(|(row, cells)| {
+            let cells: Vec<String> = cells
+                .iter()
+                .map(|val| format!("{val:0value_width$}"))
+                .collect();
+            format!("{:0addr_width$}: {}", row * 10, cells.join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Returns whether every test passed.
+// `show_mem_diff` prints the cells each failing test changed.
+pub fn run_tests(
+    source: &str,
+    filter: Option<&str>,
+    brp_mode: BrpMode,
+    style: TableStyle,
+    show_mem_diff: bool,
+) -> Result<bool, String> {
+    let program = crate::make_program(source)?;
+    print_warnings(&program.warnings);
+
+    run_program_tests(program, filter, brp_mode, style, show_mem_diff)
+}
+
+// Tests, or with `run` runs, each program in a file split up by `=== name ===`
+// lines, then lists how each one went. Returns whether all passed or halted.
+pub fn run_sections(
+    source: &str,
+    run: bool,
+    inputs: &[usize],
+    brp_mode: BrpMode,
+    style: TableStyle,
+) -> Result<bool, String> {
+    let sections = crate::sections::split(source)?;
+    let mut builder = Builder::default();
+    builder.push_record(["section", "result"]);
+    let mut all_ok = true;
+
+    for section in &sections {
+        println!("\n=== {} ===", section.name);
+
+        let (ok, result) = if run {
+            let (ok, result) = run_section(&section.source, inputs, brp_mode);
+            println!("{result}");
+            (ok, result)
+        } else {
+            match run_tests(&section.source, None, brp_mode, style, false) {
+                Ok(true) => (true, "passed".to_owned()),
+                Ok(false) => (false, "failed".to_owned()),
+                Err(e) => {
+                    println!("{e}");
+                    (false, "error".to_owned())
+                }
+            }
+        };
+
+        all_ok &= ok;
+        builder.push_record([section.name.clone(), result]);
+    }
+
+    println!("\n--- sections ---");
+    println!("{}", style.render(builder.build()));
+
+    Ok(all_ok)
+}
+
+// Runs a program without prompting for inputs, and describes how it stopped.
+fn run_section(source: &str, inputs: &[usize], brp_mode: BrpMode) -> (bool, String) {
+    let stats = match crate::make_program(source) {
+        Ok(program) => program.run_headless(inputs, MAX_STEPS, brp_mode),
+        Err(e) => return (false, format!("error: {e}")),
+    };
+
+    match stats {
+        Ok(stats) => {
+            let outputs = format_outputs(&stats.outputs, false, OutFormat::List);
+            match stats.halt_reason {
+                HaltReason::Halted { .. } => (true, format!("halted, out: {outputs}")),
+                HaltReason::StepLimit { max_steps } => (
+                    false,
+                    format!("step limit of {max_steps} reached, out: {outputs}"),
+                ),
+                HaltReason::Error(e) => (false, format!("{e}, out: {outputs}")),
+            }
+        }
+        Err(e) => (false, format!("error: {e}")),
+    }
+}
+
+// Runs the tests in `tests_source` as well as any defined in the program itself.
+pub fn run_tests_with_file(
+    source: &str,
+    tests_source: &str,
+    filter: Option<&str>,
+    brp_mode: BrpMode,
+    style: TableStyle,
+    show_mem_diff: bool,
+) -> Result<bool, String> {
+    let mut program = crate::make_program(source)?;
+    print_warnings(&program.warnings);
+
+    let tests = crate::make_tests(tests_source).map_err(|e| format!("in tests file:\n{e}"))?;
+    let program_tests = &program.parse_info.tests;
+    if let Some(test) = tests
+        .iter()
+        .find(|test| program_tests.iter().any(|t| t.name == test.name))
+    {
+        return Err(format!(
+            "in tests file:\nduplicate test name '{}' (also defined in the program)",
+            test.name
+        ));
+    }
+    program.parse_info.tests.extend(tests);
+
+    run_program_tests(program, filter, brp_mode, style, show_mem_diff)
+}
+
+fn run_program_tests(
+    program: LNCProgram,
+    filter: Option<&str>,
+    brp_mode: BrpMode,
+    style: TableStyle,
+    show_mem_diff: bool,
+) -> Result<bool, String> {
+    if let Some(advisory) = input_advisory(&program.parse_info) {
+        println!("{advisory}");
+    }
+
+    let (mem, tests) = (program.mem, program.parse_info.tests);
+    let tests = filter_tests(&tests, filter);
+
+    if tests.is_empty() {
+        if let Some(f) = filter {
+            println!("no tests match the filter \"{f}\"");
+            return Ok(true);
+        }
+    }
+
+    let mut results = vec![];
+    let mut final_mems = vec![];
+
+    for test in tests {
+        let (info, final_mem) = run_test(mem, test, brp_mode, false)?;
+        results.push(info);
+        final_mems.push(final_mem);
+    }
+
+    let (passed, failed, skipped) = count_results(&results);
+    let diffs: Vec<(String, String)> = results
+        .iter()
+        .zip(&final_mems)
+        .filter(|(info, _)| !matches!(info.result, TestResult::Passed | TestResult::Skipped))
+        .map(|(info, final_mem)| (info.name.clone(), format_mem_diff(&mem, final_mem)))
+        .collect();
+
+    println!("\n--- test results ---");
+    println!("{}", style.render(Table::new(results)));
+    if skipped > 0 {
+        println!("{passed} passed, {failed} failed, {skipped} skipped");
+    } else {
+        println!("{passed} passed, {failed} failed");
+    }
+
+    if show_mem_diff {
+        for (name, diff) in diffs {
+            println!("\n--- memory changed by {name} ---");
+            println!("{diff}");
+        }
+    }
+
+    Ok(failed == 0)
+}
+
+// The cells that differ between `initial` and `end`, as (address, initial, end).
+fn mem_diff(initial: &[usize], end: &[usize]) -> Vec<(usize, usize, usize)> {
+    initial
+        .iter()
+        .zip(end)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(addr, (&a, &b))| (addr, a, b))
+        .collect()
+}
+
+fn format_mem_diff(initial: &[usize], end: &[usize]) -> String {
+    let diff = mem_diff(initial, end);
+
+    if diff.is_empty() {
+        return "no cells changed".into();
+    }
+
+    diff.iter()
+        .map(|(addr, a, b)| format!("{addr:02}: {a:03} -> {b:03}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Runs the tests in a program, without logging them, and reports the results
+// as a JUnit XML `<testsuite>`, along with whether every test passed.
+pub fn run_tests_junit(source: &str) -> Result<(String, bool), String> {
+    let program = crate::make_program(source)?;
+
+    let mut results = vec![];
+    for test in &program.parse_info.tests {
+        results.push(run_test(program.mem, test, BrpMode::default(), true)?.0);
+    }
+
+    let (_, failed, _) = count_results(&results);
+    Ok((junit_xml(&results), failed == 0))
+}
+
+fn junit_xml(results: &[LNCTestInfo]) -> String {
+    let (_, failed, skipped) = count_results(results);
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += &format!(
+        "<testsuite name=\"lnc\" tests=\"{}\" failures=\"{failed}\" skipped=\"{skipped}\">\n",
+        results.len()
+    );
+
+    for info in results {
+        let name = xml_escape(&info.name);
+        let failure = match &info.result {
+            TestResult::Passed => {
+                xml += &format!("  <testcase name=\"{name}\"/>\n");
+                continue;
+            }
+            TestResult::Skipped => {
+                xml += &format!("  <testcase name=\"{name}\">\n    <skipped/>\n  </testcase>\n");
+                continue;
+            }
+            TestResult::Failed(msg) => msg.clone(),
+            result @ TestResult::TimedOut(_) => {
+                result.to_string().trim_start_matches("failed: ").to_owned()
+            }
+        };
+
+        let details = format!(
+            "input: {}, expected: {}, actual: {}",
+            info.input, info.expected_output, info.actual_output
+        );
+        xml += &format!(
+            "  <testcase name=\"{name}\">\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+            xml_escape(&failure),
+            xml_escape(&details)
+        );
+    }
+
+    xml + "</testsuite>\n"
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// (passed, failed, skipped)
+fn count_results(results: &[LNCTestInfo]) -> (usize, usize, usize) {
+    let count = |f: fn(&TestResult) -> bool| results.iter().filter(|info| f(&info.result)).count();
+    let passed = count(|result| matches!(result, TestResult::Passed));
+    let skipped = count(|result| matches!(result, TestResult::Skipped));
+
+    (passed, results.len() - passed - skipped, skipped)
+}
+
+pub fn run_debugger(
+    source: &str,
+    brp_mode: BrpMode,
+    style: TableStyle,
+    color: ColorChoice,
+) -> Result<(), String> {
+    let program = crate::make_program(source)?;
+    print_warnings(&program.warnings);
+
+    let addr_to_label: HashMap<usize, String> = program
+        .parse_info
+        .label_map
+        .into_iter()
+        .map(|(k, v)| (v, k))
+        .collect();
+
+    debug_image(program.mem, &addr_to_label, brp_mode, style, color)
+}
+
+pub fn debug_mem(
+    contents: &str,
+    brp_mode: BrpMode,
+    style: TableStyle,
+    color: ColorChoice,
+) -> Result<(), String> {
+    debug_image(
+        parse_mem(contents)?,
+        &HashMap::new(),
+        brp_mode,
+        style,
+        color,
+    )
+}
+
+fn debug_image(
+    mem: [usize; 100],
+    addr_to_label: &HashMap<usize, String>,
+    brp_mode: BrpMode,
+    style: TableStyle,
+    color: ColorChoice,
+) -> Result<(), String> {
+    let mut input = CLIInput::default();
+    let mut output = CLIOutput::default();
+    let mut logger = CLILogger::default();
+    let color = ansi::enabled(color);
+
+    // each pass is one run of the program, ending when it halts or when the
+    // user chooses not to restart after a runtime error
+    let ins_count = loop {
+        let mut interpreter =
+            Interpreter::new(mem, &mut input, &mut output, &mut logger).with_brp_mode(brp_mode);
+        let mut prev_mem = None;
+        let mut error = None;
+
+        while !interpreter.is_halted() {
+            println!("\n--- ins #{} ---", interpreter.ins_count());
+            print_debug_state(&interpreter, addr_to_label, &mut prev_mem, style, color);
+
+            // a successful `set` shows the state again, with the changed cell
+            let skip_count = loop {
+                match prompt_command() {
+                    DebugCommand::Step(n) => break Some(n),
+                    DebugCommand::Dump => println!("{}", state_to_json(&interpreter.state())),
+                    DebugCommand::Set { addr, val } => match interpreter.set_mem(addr, val) {
+                        Ok(()) => break None,
+                        Err(e) => println!("{e}"),
+                    },
+                }
+            };
+            let Some(skip_count) = skip_count else {
+                continue;
+            };
+            match interpreter.step_n(skip_count) {
+                Ok(ran) if ran < skip_count => {
+                    println!("\nprogram halted after {ran} of {skip_count} instructions");
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let ins_count = interpreter.ins_count();
+        let Some(e) = error else {
+            break ins_count;
+        };
+
+        // show where it went wrong, with the state it was left in
+        println!("\n--- runtime error after ins #{ins_count} ---");
+        println!("{}", make_error_table(&e, style));
+        print_debug_state(&interpreter, addr_to_label, &mut prev_mem, style, color);
+        drop(interpreter);
+
+        if !prompt_restart() {
+            break ins_count;
+        }
+        input.history.clear();
+        output.history.clear();
+    };
+
+    let mut builder = Builder::default();
+    builder.push_record(["ins_count", "in", "out"]);
+    builder.push_record([
+        ins_count.to_string(),
+        format!("{:?}", input.history),
+        format!("{:?}", output.history),
+    ]);
+
+    let result_table = style.render(builder.build());
+
+    println!("\n--- summary ---");
+    println!("{result_table}");
+
+    Ok(())
+}
+
+pub fn run_repl(style: TableStyle) -> Result<(), String> {
+    println!("Enter one instruction per line. Type \"reset\" to clear state, \"quit\" to exit.");
+
+    let mut repl = Repl::default();
+
+    let mut input = CLIInput::default();
+    let mut output = CLIOutput::default();
+    let mut logger = CLILogger::default();
+
+    loop {
+        print!("lnc> ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(e) => return Err(format!("Error: {e:?}")),
+        }
+
+        match line.trim() {
+            "" => continue,
+            "quit" | "exit" => break,
+            "reset" => {
+                repl = Repl::default();
+                println!("state cleared");
+            }
+            line => match repl.eval(line, &mut input, &mut output, &mut logger) {
+                Ok(()) => println!("{}", make_state_table(&repl.state, style)),
+                Err(e) => println!("{e}"),
+            },
+        }
+    }
+
+    Ok(())
+}
+
+struct Repl {
+    lines: Vec<String>,
+    state: InterpreterState,
+    next_addr: usize,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self {
+            lines: vec![],
+            state: InterpreterState {
+                mem: [0; 100],
+                pc: 0,
+                acc: 0,
+                neg_flag: false,
+                halted: false,
+            },
+            next_addr: 0,
+        }
+    }
+}
+
+impl Repl {
+    // Assembles `line` into the next free memory cell(s) and, unless it is
+    // data, executes it. On error the state is left untouched.
+    fn eval<I: Input, O: Output, L: Log>(
+        &mut self,
+        line: &str,
+        input: &mut I,
+        output: &mut O,
+        logger: &mut L,
+    ) -> Result<(), String> {
+        let mut lines = self.lines.clone();
+        lines.push(line.to_owned());
+
+        let program = crate::make_program(&lines.join("\n"))?;
+
+        if !program.parse_info.tests.is_empty() {
+            return Err("tests cannot be defined in the REPL".into());
+        }
+        // a `call` saves the accumulator in a variable, and is several
+        // instructions including a branch
+        if program
+            .parse_info
+            .vars
+            .iter()
+            .any(|var| var == parse::CALL_ACC)
+        {
+            return Err("call and ret cannot be used in the REPL".into());
+        }
+        // they would move every time a line is added
+        if !program.parse_info.vars.is_empty() {
+            return Err("variables cannot be declared in the REPL, use a dat instead".into());
+        }
+
+        let info = &program.parse_info;
+        let instructions = &info.instructions;
+        // the cells for the values of `ldi` and friends come after the code,
+        // so they move every time a line is added
+        let code_len = instructions.len() - info.vars.len() - info.consts.len();
+        let reads_const = |addr: &Address| match addr {
+            Address::Symbolic(label) => {
+                info.consts.iter().any(|&v| *label == parse::const_label(v))
+            }
+            _ => false,
+        };
+        let mut state = self.state.clone();
+
+        for (addr, ins) in instructions.iter().enumerate() {
+            let moved = match ins {
+                Instruction::Load(a) | Instruction::Add(a) | Instruction::Subtract(a) => {
+                    reads_const(a)
+                }
+                _ => false,
+            };
+            if addr >= self.next_addr || moved {
+                state.mem[addr] = program.mem[addr];
+            }
+        }
+
+        // every instruction on the line runs in order, as if there were no branches
+        let to_execute: Vec<usize> = (self.next_addr..code_len)
+            .filter(|&addr| !matches!(instructions[addr], Instruction::Data(_)))
+            .collect();
+        if !to_execute.is_empty() {
+            state.halted = false;
+        }
+
+        for addr in to_execute {
+            if state.halted {
+                break;
+            }
+            state.pc = addr;
+
+            let mut interpreter =
+                Interpreter::from_state(state, &mut *input, &mut *output, &mut *logger);
+            interpreter.step()?;
+            state = interpreter.state();
+        }
+
+        self.next_addr = code_len;
+        self.lines = lines;
+        self.state = state;
+
+        Ok(())
+    }
+}
+
+fn print_debug_state(
+    interpreter: &Interpreter<CLIInput, CLIOutput, CLILogger>,
+    addr_to_label: &HashMap<usize, String>,
+    prev_mem: &mut Option<[usize; 100]>,
+    style: TableStyle,
+    color: bool,
+) {
+    let state = interpreter.state();
+
+    let mem_table = make_mem_table(&state, addr_to_label, 15, style);
+    if color {
+        println!(
+            "{}",
+            color_mem_table(&mem_table, &state, prev_mem.as_ref(), 15, style)
+        );
+    } else {
+        println!("{mem_table}");
+    }
+    *prev_mem = Some(state.mem);
+    println!("{}", make_state_table(&state, style));
+    println!(
+        "{}",
+        make_io_table(
+            &interpreter.input().history,
+            &interpreter.output().history,
+            style
+        )
+    );
+}
+
+// Anything but yes, including EOF, is no.
+fn prompt_restart() -> bool {
+    print!("restart the program? [y/N] ");
+    let _ = io::stdout().flush();
+
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input).is_err() {
+        return false;
+    }
+
+    matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn make_error_table(e: &LNCError, style: TableStyle) -> String {
+    let mut builder = Builder::default();
+
+    match e {
+        LNCError::Runtime { pc, msg } => {
+            builder.push_record(["address", "error"]);
+            builder.push_record([pc.to_string(), msg.to_owned()]);
+        }
+        e => {
+            builder.push_record(["error"]);
+            builder.push_record([e.to_string()]);
+        }
+    }
+
+    style.render(builder.build())
+}
+
+#[derive(Debug, PartialEq)]
+enum DebugCommand {
+    // run this many instructions
+    Step(usize),
+    // `set ADDR VALUE`: write a value into memory
+    Set { addr: usize, val: usize },
+    // print the registers and memory as JSON
+    Dump,
+}
+
+fn prompt_command() -> DebugCommand {
+    loop {
+        print!(">>> ");
+        let _ = io::stdout().flush();
+
+        let mut input = String::new();
+
+        if io::stdin().read_line(&mut input).is_err() {
+            continue;
+        }
+
+        match parse_command(input.trim()) {
+            Ok(Some(command)) => return command,
+            Ok(None) => (),
+            Err(e) => println!("{e}"),
+        }
+    }
+}
+
+// Nothing means one step. Returns `None` for input that isn't a command, which
+// is ignored.
+fn parse_command(input: &str) -> Result<Option<DebugCommand>, String> {
+    if input.is_empty() {
+        return Ok(Some(DebugCommand::Step(1)));
+    }
+
+    if let Ok(n) = input.parse::<usize>() {
+        return Ok(Some(DebugCommand::Step(n.max(1))));
+    }
+
+    let mut words = input.split_whitespace();
+    match words.next() {
+        Some("dump") if words.next().is_none() => Ok(Some(DebugCommand::Dump)),
+        Some("set") => {
+            let args: Vec<_> = words.map(str::parse::<usize>).collect();
+            match args[..] {
+                [Ok(addr), Ok(val)] => Ok(Some(DebugCommand::Set { addr, val })),
+                _ => Err("usage: set ADDR VALUE".into()),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+// A single line, to paste into a bug report or read from a script.
+fn state_to_json(state: &InterpreterState) -> String {
+    let mem: Vec<String> = state.mem.iter().map(usize::to_string).collect();
+    format!(
+        "{{\"pc\":{},\"acc\":{},\"neg_flag\":{},\"halted\":{},\"mem\":[{}]}}",
+        state.pc,
+        state.acc,
+        state.neg_flag,
+        state.halted,
+        mem.join(",")
+    )
+}
+
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        println!("{warning}");
+    }
+}
+
+fn make_mem_table(
+    state: &InterpreterState,
+    addr_to_label: &HashMap<usize, String>,
+    num_lines: usize,
+    style: TableStyle,
+) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(["pc", "addr", "label", "mnemonic", "mem"]);
+
+    let (min, max) = mem_table_window(state.pc, num_lines);
+
+    for (addr, val) in state
+        .mem
+        .iter()
+        .enumerate()
+        .filter(|(addr, _)| *addr >= min && *addr <= max)
+    {
+        let arrow = if addr == state.pc { ">" } else { "" };
+        let addr_str = format!("{addr:02}");
+        let label = if let Some(l) = addr_to_label.get(&addr) {
+            l
+        } else {
+            ""
+        };
+
+        let mnemonic = disasm::mnemonic(*val);
+        let val_str = format!("{:03}", val);
+
+        builder.push_record([arrow, &addr_str, label, &mnemonic, &val_str]);
+    }
+
+    style.render(builder.build())
+}
+
+fn mem_table_window(pc: usize, num_lines: usize) -> (usize, usize) {
+    if pc < num_lines / 2 {
+        (0, num_lines - 1)
+    } else if pc > (99 - num_lines / 2) {
+        (99 - num_lines + 1, 99)
+    } else {
+        (pc - num_lines / 2, pc + num_lines / 2)
+    }
+}
+
+// Colors the rendered table rather than its cells so that tabled measures the
+// column widths without the escape codes. The row at the pc is yellow and a
+// mem cell that changed since `prev_mem` is green.
+fn color_mem_table(
+    table: &str,
+    state: &InterpreterState,
+    prev_mem: Option<&[usize; 100]>,
+    num_lines: usize,
+    style: TableStyle,
+) -> String {
+    let (min, _) = mem_table_window(state.pc, num_lines);
+
+    let lines: Vec<String> = table
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            // skip the top border, the header and the line below it
+            let Some(row) = i.checked_sub(3).filter(|row| *row < num_lines) else {
+                return line.to_owned();
+            };
+            let addr = min + row;
+
+            let is_pc = addr == state.pc;
+            let changed = prev_mem.is_some_and(|prev| prev[addr] != state.mem[addr]);
+
+            let mut cells: Vec<String> = line.split(style.vertical()).map(str::to_owned).collect();
+            let mem_col = cells.len() - 2;
+            for (col, cell) in cells.iter_mut().enumerate().take(mem_col + 1).skip(1) {
+                if col == mem_col && changed {
+                    *cell = ansi::paint(cell, Color::Green);
+                } else if is_pc {
+                    *cell = ansi::paint(cell, Color::Yellow);
+                }
+            }
+
+            cells.join(&style.vertical().to_string())
+        })
+        .collect();
+
+    lines.join("\n")
+}
+
+fn make_state_table(state: &InterpreterState, style: TableStyle) -> String {
+    let mut builder = Builder::default();
+
+    builder.push_record(["pc", "acc", "neg_flag", "halted"]);
+    builder.push_record([
+        state.pc.to_string(),
+        state.acc.to_string(),
+        state.neg_flag.to_string(),
+        state.halted.to_string(),
+    ]);
+
+    style.render(builder.build())
+}
+
+fn input_advisory(parse_info: &ParseInfo) -> Option<String> {
+    let reads_input = parse_info
+        .instructions
+        .iter()
+        .any(|ins| matches!(ins, Instruction::Input | Instruction::InputDevice(_)));
+
+    let names: Vec<&str> = parse_info
+        .tests
+        .iter()
+        .filter(|test| !test.inputs.is_empty())
+        .map(|test| test.name.as_str())
+        .collect();
+
+    if reads_input || names.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "note: the program has no inp instruction, but these tests provide inputs: {}",
+        names.join(", ")
+    ))
+}
+
+fn filter_tests<'a>(tests: &'a [LNCTest], filter: Option<&str>) -> Vec<&'a LNCTest> {
+    tests
+        .iter()
+        .filter(|test| filter.is_none_or(|f| test.name.contains(f)))
+        .collect()
+}
+
+fn make_io_table(inputs: &[usize], outputs: &[usize], style: TableStyle) -> String {
+    let mut builder = Builder::default();
+
+    builder.push_record(["in", "out"]);
+    builder.push_record([format!("{inputs:?}"), format!("{outputs:?}")]);
+
+    style.render(builder.build())
+}
+
+// Also returns the memory as it was when the test stopped. With `quiet`, the
+// instructions aren't logged.
+fn run_test(
+    mem: [usize; 100],
+    test: &LNCTest,
+    brp_mode: BrpMode,
+    quiet: bool,
+) -> Result<(LNCTestInfo, [usize; 100]), String> {
+    if test.skipped {
+        let info = LNCTestInfo {
+            actual_output: "-".into(),
+            ..LNCTestInfo::new(test, &[], 0, TestResult::Skipped)
+        };
+        return Ok((info, mem));
+    }
+
+    let mut input = QueueInput::new(&test.inputs)?;
+    let mut output = StackOutput::default();
+    let mut logger = CLILogger {
+        quiet,
+        ..Default::default()
+    };
+
+    let mut interpreter =
+        Interpreter::new(mem, &mut input, &mut output, &mut logger).with_brp_mode(brp_mode);
+
+    let ins_count = match interpreter.run_to_halt(MAX_STEPS) {
+        Ok(n) => n,
+        Err(e) => {
+            let ins_count = interpreter.ins_count();
+            let mem = interpreter.state().mem;
+            // a hang never counts as the runtime error a test may expect
+            let result = match e {
+                LNCError::StepLimit { max_steps } => TestResult::TimedOut(max_steps),
+                _ if test.expect_error => TestResult::Passed,
+                LNCError::Runtime { msg, .. } if msg == EMPTY_QUEUE => TestResult::Failed(format!(
+                    "program requested more inputs than provided ({} available)",
+                    input.provided
+                )),
+                e => TestResult::Failed(e.to_string()),
+            };
+
+            return Ok((
+                LNCTestInfo::new(test, &output.stack, ins_count, result),
+                mem,
+            ));
+        }
+    };
+    let mem = interpreter.state().mem;
+
+    if test.expect_error {
+        return Ok((
+            LNCTestInfo::new(
+                test,
+                &output.stack,
+                ins_count,
+                TestResult::Failed("expected a runtime error".into()),
+            ),
+            mem,
+        ));
+    }
+
+    if !input.queue.is_empty() {
+        return Ok((
+            LNCTestInfo::new(
+                test,
+                &output.stack,
+                ins_count,
+                TestResult::Failed(format!("unused inputs: {:?}", input.queue)),
+            ),
+            mem,
+        ));
+    }
+
+    if output.stack != test.outputs {
+        return Ok((
+            LNCTestInfo::new(
+                test,
+                &output.stack,
+                ins_count,
+                TestResult::Failed("incorrect outputs".into()),
+            ),
+            mem,
+        ));
+    }
+
+    if let Some(&(addr, expected)) = test.mem_checks.iter().find(|&&(a, v)| mem[a] != v) {
+        return Ok((
+            LNCTestInfo::new(
+                test,
+                &output.stack,
+                ins_count,
+                TestResult::Failed(format!("mem[{addr}] is {}, expected {expected}", mem[addr])),
+            ),
+            mem,
+        ));
+    }
+
+    Ok((
+        LNCTestInfo::new(test, &output.stack, ins_count, TestResult::Passed),
+        mem,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec_io::NullLogger;
+
+    // the stats of a run that stopped for `reason` without doing anything
+    fn stats(reason: HaltReason) -> RunStats {
+        RunStats {
+            ins_count: 0,
+            max_acc: 0,
+            overflows: 0,
+            underflows: 0,
+            reason,
+            program_len: 0,
+            acc: 0,
+            neg_flag: false,
+            pc: 0,
+            elapsed: None,
+        }
+    }
+
+    fn get_tests(source: &str) -> Vec<LNCTest> {
+        crate::make_program(source).unwrap().parse_info.tests
+    }
+
+    #[test]
+    fn check_reports_all_errors() {
+        assert!(check("inp\nout\nhlt", false).is_ok());
+        assert!(check("x: dat 5\nbra x", false).is_ok());
+        // the dat is branched to, so it is only a warning unless strict
+        assert_eq!(
+            check("x: dat 5\nbra x", true).unwrap_err(),
+            "error @ address 0: \"dat 5\" can be reached as code, but 005 is an undefined instruction\n1 error"
+        );
+
+        let err = check("lda\nfoo bar\nadd missing", false).unwrap_err();
+        assert!(err.ends_with("\n3 errors"), "{err}");
+        assert_eq!(err.lines().count(), 4);
+
+        assert_eq!(
+            check("bra nowhere", false).unwrap_err(),
+            "Label 'nowhere' is not defined (used by instruction at address 0)\n1 error"
+        );
+    }
+
+    #[test]
+    fn input_shows_custom_prompt() {
+        let mut input = CLIInput::with_prompt("Enter the first number: ");
+        let mut reader = io::Cursor::new("12\nabc\n");
+        let mut writer = vec![];
+
+        let num = input.read_from(&mut reader, &mut writer).unwrap();
+        assert_eq!(usize::from(num), 12);
+        assert!(input.read_from(&mut reader, &mut writer).is_err());
+        assert_eq!(
+            String::from_utf8(writer).unwrap(),
+            "Enter the first number: Enter the first number: \
+             Error with input \"abc\": invalid digit found in string, try again\n\
+             Enter the first number: "
+        );
+        assert_eq!(input.history, vec![12]);
+
+        let mut writer = vec![];
+        let _ = CLIInput::default().read_from(&mut io::Cursor::new("1\n"), &mut writer);
+        assert_eq!(writer, DEFAULT_PROMPT.as_bytes());
+    }
+
+    #[test]
+    fn input_retries_invalid_values() {
+        let mut input = CLIInput::with_prompt("> ");
+        let mut reader = io::Cursor::new("abc\n1000\n\n42\n");
+        let mut writer = vec![];
+
+        let num = input.read_from(&mut reader, &mut writer).unwrap();
+        assert_eq!(usize::from(num), 42);
+        assert_eq!(input.history, vec![42]);
+        assert_eq!(
+            String::from_utf8(writer).unwrap(),
+            "> Error with input \"abc\": invalid digit found in string, try again
+> Error: input 1000 is too large (must be below 1000), try again
+> Error with input \"\": cannot parse integer from empty string, try again
+> "
+        );
+
+        // EOF ends the run rather than prompting forever
+        let mut writer = vec![];
+        assert_eq!(
+            input
+                .read_from(&mut io::Cursor::new("x\n"), &mut writer)
+                .map(usize::from),
+            Err("Error: no more input (end of file)".into())
+        );
+
+        let invalid = "x\n".repeat(MAX_INPUT_ATTEMPTS + 1);
+        assert_eq!(
+            input
+                .read_from(&mut io::Cursor::new(invalid), &mut vec![])
+                .map(usize::from),
+            Err(format!(
+                "Error: gave up after {MAX_INPUT_ATTEMPTS} invalid inputs"
+            ))
+        );
+    }
+
+    #[test]
+    fn branch_warnings_ignore_quiet_and_explain() {
+        // bra 50
+        let mut mem = [0; 100];
+        mem[0] = 650;
+
+        for (quiet, explain) in [(true, false), (false, true)] {
+            let mut input = QueueInput::default();
+            let mut output = StackOutput::default();
+            let mut logger = CLILogger {
+                quiet,
+                explain,
+                w: vec![],
+            };
+
+            Interpreter::new(mem, &mut input, &mut output, &mut logger)
+                .with_branch_checks(1)
+                .run_to_halt(10)
+                .unwrap();
+
+            let printed = String::from_utf8(logger.w).unwrap();
+            assert!(
+                printed.starts_with(
+                    "warning @ address 0: branch to address 50, past the end of the program\n"
+                ),
+                "{printed}"
+            );
+        }
+    }
+
+    #[test]
+    fn repl_executes_each_line() {
+        let mut repl = Repl::default();
+
+        let mut input = QueueInput::new(&[5]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut eval =
+            |repl: &mut Repl, line| repl.eval(line, &mut input, &mut output, &mut logger);
+
+        eval(&mut repl, "inp").unwrap();
+        assert_eq!(repl.state.acc, 5);
+
+        eval(&mut repl, "sto 50").unwrap();
+        assert_eq!(repl.state.mem[50], 5);
+
+        eval(&mut repl, "add 50").unwrap();
+        assert_eq!(repl.state.acc, 10);
+
+        // errors leave the state untouched
+        assert!(eval(&mut repl, "lda").is_err());
+        assert!(eval(&mut repl, "add ten").is_err());
+        assert_eq!(repl.next_addr, 3);
+
+        // data is stored but not executed
+        eval(&mut repl, "ten: dat 10").unwrap();
+        assert_eq!(repl.state.mem[3], 10);
+        assert_eq!(repl.state.acc, 10);
+
+        eval(&mut repl, "sub ten").unwrap();
+        assert_eq!(repl.state.acc, 0);
+        assert_eq!(repl.state.mem[..5], [901, 350, 150, 10, 203]);
+
+        eval(&mut repl, "out").unwrap();
+        eval(&mut repl, "hlt").unwrap();
+        assert!(repl.state.halted);
+
+        eval(&mut repl, "add ten").unwrap();
+        assert_eq!(repl.state.acc, 10);
+        assert!(!repl.state.halted);
+
+        assert_eq!(output.stack, vec![0]);
+    }
+
+    #[test]
+    fn repl_immediate_values() {
+        let mut repl = Repl::default();
+
+        let mut input = QueueInput::default();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut eval =
+            |repl: &mut Repl, line| repl.eval(line, &mut input, &mut output, &mut logger);
+
+        eval(&mut repl, "ldi 5").unwrap();
+        assert_eq!(repl.state.acc, 5);
+        eval(&mut repl, "out").unwrap();
+        assert_eq!(repl.next_addr, 2);
+
+        // the constant moved past `out`, and so did the `ldi` reading it
+        eval(&mut repl, "addi 2").unwrap();
+        assert_eq!(repl.state.acc, 7);
+        assert_eq!(repl.state.mem[..5], [503, 902, 104, 5, 2]);
+
+        assert_eq!(output.stack, vec![5]);
+    }
+
+    #[test]
+    fn repl_runs_every_instruction_on_a_line() {
+        let mut repl = Repl::default();
+
+        let mut input = QueueInput::new(&[4]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut eval =
+            |repl: &mut Repl, line| repl.eval(line, &mut input, &mut output, &mut logger);
+
+        eval(&mut repl, "x: dat 7").unwrap();
+        eval(&mut repl, "lda x | out | dat 1 | inp | out").unwrap();
+        assert_eq!(repl.state.acc, 4);
+        assert_eq!(repl.next_addr, 6);
+
+        // nothing after a hlt runs
+        eval(&mut repl, "hlt | out").unwrap();
+        assert!(repl.state.halted);
+
+        assert_eq!(output.stack, vec![7, 4]);
+    }
+
+    #[test]
+    fn repl_rejects_vars() {
+        let mut repl = Repl::default();
+
+        let mut input = QueueInput::default();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut eval =
+            |repl: &mut Repl, line| repl.eval(line, &mut input, &mut output, &mut logger);
+
+        eval(&mut repl, "lda 90").unwrap();
+        assert_eq!(
+            eval(&mut repl, "x var"),
+            Err("variables cannot be declared in the REPL, use a dat instead".into())
+        );
+        assert_eq!(repl.next_addr, 1);
+
+        // later lines still run
+        eval(&mut repl, "x: dat 3").unwrap();
+        eval(&mut repl, "add x").unwrap();
+        assert_eq!(repl.state.acc, 3);
+    }
+
+    #[test]
+    fn repl_rejects_call() {
+        let mut repl = Repl::default();
+
+        let mut input = QueueInput::default();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut eval =
+            |repl: &mut Repl, line| repl.eval(line, &mut input, &mut output, &mut logger);
+
+        assert!(eval(&mut repl, "f: out | ret").is_err());
+        assert_eq!(
+            eval(&mut repl, "f: out | ret | call f"),
+            Err("call and ret cannot be used in the REPL".into())
+        );
+        assert_eq!(repl.next_addr, 0);
+    }
+
+    #[test]
+    fn colors_pc_row_and_changed_cells() {
+        let prev = InterpreterState::new([0; 100]);
+        let mut state = prev.clone();
+        state.pc = 1;
+        state.mem[3] = 5;
+
+        let table = make_mem_table(&state, &HashMap::new(), 15, TableStyle::Sharp);
+        let colored = color_mem_table(&table, &state, Some(&prev.mem), 15, TableStyle::Sharp);
+        let lines: Vec<&str> = colored.lines().collect();
+
+        assert_eq!(lines.len(), table.lines().count());
+        assert!(!lines[3].contains('\x1B'));
+        assert_eq!(lines[4].matches("\x1B[33m").count(), 5);
+        assert!(lines[6].ends_with(&format!("{}│", ansi::paint(" 005 ", Color::Green))));
+
+        // nothing has changed on the first step
+        let colored = color_mem_table(&table, &state, None, 15, TableStyle::Sharp);
+        assert!(!colored.contains("\x1B[32m"));
+    }
+
+    #[test]
+    fn ascii_tables() {
+        let mut state = InterpreterState::new([0; 100]);
+        state.mem[3] = 5;
+
+        let table = make_state_table(&state, TableStyle::Ascii);
+        assert_eq!(
+            table,
+            "\
++----+-----+----------+--------+
+| pc | acc | neg_flag | halted |
++----+-----+----------+--------+
+| 0  | 0   | false    | false  |
++----+-----+----------+--------+"
+        );
+
+        let table = make_mem_table(&state, &HashMap::new(), 15, TableStyle::Ascii);
+        assert!(table.is_ascii());
+        // one line per cell between the header and bottom border, as with the sharp style
+        assert_eq!(table.lines().count(), 15 + 4);
+
+        let colored = color_mem_table(&table, &state, Some(&[0; 100]), 15, TableStyle::Ascii);
+        let lines: Vec<&str> = colored.lines().collect();
+        assert!(lines[6].ends_with(&format!("{}|", ansi::paint(" 005 ", Color::Green))));
+    }
+
+    #[test]
+    fn format_outputs_styles() {
+        let outputs = [1, 20, 997];
+
+        assert_eq!(
+            format_outputs(&outputs, false, OutFormat::List),
+            format!("{outputs:?}")
+        );
+        assert_eq!(
+            format_outputs(&outputs, false, OutFormat::Lines),
+            "1\n20\n997"
+        );
+        assert_eq!(
+            format_outputs(&outputs, false, OutFormat::Comma),
+            "1,20,997"
+        );
+        assert_eq!(
+            format_outputs(&outputs, false, OutFormat::Json),
+            "[1,20,997]"
+        );
+        assert_eq!(format_outputs(&outputs, true, OutFormat::Json), "[1,20,-3]");
+
+        assert_eq!(format_outputs(&[], false, OutFormat::List), "[]");
+        assert_eq!(format_outputs(&[], false, OutFormat::Lines), "");
+        assert_eq!(format_outputs(&[], false, OutFormat::Json), "[]");
+    }
+
+    #[test]
+    fn disasm_table_lists_used_cells() {
+        let program = crate::make_program("inp\nout\nhlt\nhlt").unwrap();
+        let addr_to_label = HashMap::from([(5, "end".to_owned())]);
+
+        let table = make_disasm_table(&program.mem, &addr_to_label, &[], 4, TableStyle::Sharp);
+        let rows: Vec<&str> = table.lines().skip(3).collect();
+
+        // the labelled cell is included, plus the bottom border
+        assert_eq!(rows.len(), 7);
+        assert!(rows[0].contains("inp") && rows[0].contains("901"));
+        assert!(rows[3].contains("hlt"));
+        assert!(rows[5].contains("end"));
+
+        assert_eq!(used_len(&program.mem), 2);
+        assert_eq!(used_len(&[0; 100]), 1);
+    }
+
+    #[test]
+    fn parse_debugger_commands() {
+        assert_eq!(parse_command(""), Ok(Some(DebugCommand::Step(1))));
+        assert_eq!(parse_command("10"), Ok(Some(DebugCommand::Step(10))));
+        assert_eq!(parse_command("0"), Ok(Some(DebugCommand::Step(1))));
+        assert_eq!(
+            parse_command("set 50 123"),
+            Ok(Some(DebugCommand::Set { addr: 50, val: 123 }))
+        );
+        assert_eq!(parse_command("set 50"), Err("usage: set ADDR VALUE".into()));
+        assert_eq!(
+            parse_command("set a b"),
+            Err("usage: set ADDR VALUE".into())
+        );
+        assert_eq!(parse_command("hello"), Ok(None));
+        assert_eq!(parse_command("dump"), Ok(Some(DebugCommand::Dump)));
+        assert_eq!(parse_command("dump 5"), Ok(None));
+    }
+
+    #[test]
+    fn state_as_json() {
+        let mut mem = [0; 100];
+        mem[..3].copy_from_slice(&[901, 902, 7]);
+        let state = InterpreterState {
+            mem,
+            pc: 2,
+            acc: 7,
+            neg_flag: true,
+            halted: false,
+        };
+
+        let zeros = vec!["0"; 97].join(",");
+        assert_eq!(
+            state_to_json(&state),
+            format!(
+                r#"{{"pc":2,"acc":7,"neg_flag":true,"halted":false,"mem":[901,902,7,{zeros}]}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn xref_table_lists_references() {
+        let source = "
+            loop: lda count
+            sub one
+            sto count
+            brz done
+            bra loop
+            done: lda count+1
+            call f
+            hlt
+            f: out
+            ret
+            count: dat 3
+            one: dat 1
+            spare: dat
+            x var";
+        let program = crate::make_program(source).unwrap();
+        let table = make_xref_table(&program.parse_info, &program.mem, TableStyle::Ascii);
+
+        let rows: Vec<Vec<&str>> = table
+            .lines()
+            .filter(|row| row.starts_with('|'))
+            .map(|row| row.split('|').map(str::trim).collect())
+            .collect();
+
+        assert_eq!(
+            rows[0][1..],
+            ["label", "addr", "kind", "value", "referenced by", ""]
+        );
+        assert_eq!(rows[1][1..6], ["loop", "00", "code", "lda 15", "04"]);
+        assert_eq!(rows[2][1..6], ["done", "05", "code", "lda 16", "03"]);
+        assert_eq!(rows[3][1..6], ["f", "13", "code", "out", "10"]);
+        assert_eq!(rows[4][1..6], ["count", "15", "dat", "003", "00, 02, 05"]);
+        assert_eq!(rows[5][1..6], ["one", "16", "dat", "001", "01"]);
+        assert_eq!(rows[6][1..6], ["spare", "17", "dat", "000", "unreferenced"]);
+        assert_eq!(rows[7][1..6], ["x", "19", "var", "000", "unreferenced"]);
+        assert_eq!(rows.len(), 8);
+    }
+
+    #[test]
+    fn disasm_table_shows_source_lines() {
+        let program = crate::make_program("inp\n\nloop: out\n; comment\nbra loop\nx var").unwrap();
+        let info = &program.parse_info;
+        let table = make_disasm_table(
+            &program.mem,
+            &HashMap::new(),
+            &info.lines,
+            4,
+            TableStyle::Sharp,
+        );
+
+        let lines: Vec<Vec<&str>> = table
+            .lines()
+            .skip(3)
+            .map(|row| row.split('│').map(str::trim).collect())
+            .collect();
+
+        assert!(table.lines().nth(1).unwrap().contains("line"));
+        assert_eq!(lines[0][1..3], ["00", "1"]);
+        assert_eq!(lines[1][1..3], ["01", "3"]);
+        assert_eq!(lines[2][1..3], ["02", "5"]);
+        assert_eq!(lines[3][1..3], ["03", "6"]);
+    }
+
+    #[test]
+    fn summary_after_runtime_error() {
+        // inp, out, out, then the undefined 400
+        let mut mem = [0; 100];
+        mem[..4].copy_from_slice(&[901, 902, 902, 400]);
+        let options = RunOptions {
+            inputs: vec![5],
+            quiet: true,
+            ..Default::default()
+        };
+
+        let mut summary = vec![];
+        let res = run_image_to(&mut summary, mem, &options, 4, &[]);
+        assert_eq!(
+            res,
+            Err("error at address 3: 400 is an undefined instruction".into())
+        );
+
+        assert_eq!(
+            String::from_utf8(summary).unwrap(),
+            "
+--- summary ---
+stopped by: runtime error
+instruction count: 3
+peak acc: 5
+final acc: 5, neg_flag: false, pc: 4
+overflows: 0, underflows: 0
+in:  [5]
+out: [5, 5]
+"
+        );
+    }
+
+    #[test]
+    fn profile_table_lists_hottest_first() {
+        let program = crate::make_program("inp\nloop: sub one\nbrp loop\nhlt\none: dat 1").unwrap();
+        let mut profile = [0; 100];
+        profile[..4].copy_from_slice(&[1, 4, 4, 1]);
+
+        let table = make_profile_table(&profile, &program.mem, TableStyle::Ascii);
+        let rows: Vec<Vec<&str>> = table
+            .lines()
+            .filter(|row| row.starts_with('|'))
+            .map(|row| row.split('|').map(str::trim).collect())
+            .collect();
+
+        assert_eq!(rows[0][1..5], ["addr", "mnemonic", "count", "share"]);
+        assert_eq!(rows[1][1..5], ["01", "sub 04", "4", "40.0%"]);
+        assert_eq!(rows[2][1..5], ["02", "brp 01", "4", "40.0%"]);
+        assert_eq!(rows[3][1..5], ["00", "inp", "1", "10.0%"]);
+        assert_eq!(rows[4][1..5], ["03", "hlt", "1", "10.0%"]);
+        assert_eq!(rows.len(), 5);
+    }
+
+    #[test]
+    fn run_each_section() {
+        let sections = crate::sections::split(
+            "=== adder ===\ninp\nadd one\nout\nhlt\none: dat 1\n=== stuck ===\nloop: bra loop\n\
+             === sign ===\ninp\nbrp pos\nout\nhlt\npos: hlt",
+        )
+        .unwrap();
+        let run = |i: usize, inputs: &[usize]| {
+            run_section(&sections[i].source, inputs, BrpMode::default())
+        };
+
+        assert_eq!(run(0, &[4]), (true, "halted, out: [5]".to_owned()));
+        assert_eq!(
+            run(1, &[]),
+            (false, format!("step limit of {MAX_STEPS} reached, out: []"))
+        );
+        assert_eq!(
+            run(0, &[]),
+            (false, "error: input queue is empty!, out: []".to_owned())
+        );
+
+        // only a strict brp falls through on 0
+        assert_eq!(run(2, &[0]), (true, "halted, out: []".to_owned()));
+        assert_eq!(
+            run_section(&sections[2].source, &[0], BrpMode::Positive),
+            (true, "halted, out: [0]".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_and_apply_data() {
+        assert_eq!(parse_data_arg("50=123"), Ok((50, 123)));
+        assert_eq!(parse_data_arg(" 7 = 0 "), Ok((7, 0)));
+        assert_eq!(
+            parse_data_arg("50"),
+            Err("invalid data \"50\": expected ADDR=VALUE".into())
+        );
+        assert!(parse_data_arg("50=x").is_err());
+        assert!(parse_data_arg("-1=5").is_err());
+
+        let program = crate::make_program("lda 50\nout\nhlt\nx: dat 4").unwrap();
+        let code = code_ranges(&program.parse_info);
+        let mut mem = program.mem;
+
+        let warnings = apply_data(&mut mem, &[(50, 123), (3, 5)], &code).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!((mem[50], mem[3]), (123, 5));
+
+        let warnings = apply_data(&mut mem, &[(1, 901)], &code).unwrap();
+        assert_eq!(
+            warnings,
+            ["warning: data 1=901 overwrites the instruction at address 1"]
+        );
+        assert_eq!(mem[1], 901);
+
+        assert_eq!(
+            apply_data(&mut mem, &[(100, 1)], &code),
+            Err("invalid data 100=1: address must be 0-99".into())
+        );
+        let options = RunOptions {
+            data: vec![(100, 1)],
+            check_uninit: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            run_bin(&program.to_bytes(), &options),
+            Err("invalid data 100=1: address must be 0-99".into())
+        );
+        assert_eq!(
+            apply_data(&mut mem, &[(10, 1000)], &code),
+            Err("invalid data 10=1000: value must be 0-999".into())
+        );
+    }
+
+    #[test]
+    fn summary_shows_timing() {
+        let stats = RunStats {
+            ins_count: 1000,
+            elapsed: Some(Duration::from_millis(250)),
+            ..stats(HaltReason::Halted { addr: 3 })
+        };
+
+        let mut summary = vec![];
+        write_summary(&mut summary, &stats, &[], &[], &RunOptions::default()).unwrap();
+        assert!(String::from_utf8(summary)
+            .unwrap()
+            .contains("\ntime: 250.000 ms (4000 instructions/s)\n"));
+
+        assert_eq!(
+            format_timing(5, Duration::ZERO),
+            "0.000 ms (- instructions/s)"
+        );
+    }
+
+    #[test]
+    fn no_summary() {
+        let stats = RunStats {
+            ins_count: 1,
+            program_len: 1,
+            pc: 1,
+            ..stats(HaltReason::Halted { addr: 0 })
+        };
+        let options = RunOptions {
+            no_summary: true,
+            ..Default::default()
+        };
+
+        let mut summary = vec![];
+        write_summary(&mut summary, &stats, &[1], &[2], &options).unwrap();
+        assert!(summary.is_empty());
+    }
+
+    #[test]
+    fn summary_shows_final_registers() {
+        let stats = RunStats {
+            ins_count: 4,
+            max_acc: 990,
+            underflows: 1,
+            program_len: 6,
+            acc: 990,
+            neg_flag: true,
+            pc: 4,
+            ..stats(HaltReason::Halted { addr: 3 })
+        };
+
+        let summary = |options| {
+            let mut summary = vec![];
+            write_summary(&mut summary, &stats, &[], &[], &options).unwrap();
+            String::from_utf8(summary).unwrap()
+        };
+
+        assert!(
+            summary(RunOptions::default()).contains("\nfinal acc: 990, neg_flag: true, pc: 4\n")
+        );
+        let signed = RunOptions {
+            signed_output: true,
+            ..Default::default()
+        };
+        assert!(summary(signed).contains("\nfinal acc: -10, neg_flag: true, pc: 4\n"));
+    }
+
+    #[test]
+    fn describes_why_the_run_stopped() {
+        let stats = |reason| RunStats {
+            program_len: 5,
+            ..stats(reason)
+        };
+
+        assert_eq!(
+            stats(HaltReason::Halted { addr: 4 }).describe_stop(),
+            "hlt at address 4"
+        );
+        assert_eq!(
+            stats(HaltReason::Halted { addr: 5 }).describe_stop(),
+            "hlt at address 5, past the end of the program (empty memory?)"
+        );
+        assert_eq!(
+            stats(HaltReason::StepLimit { max_steps: 10 }).describe_stop(),
+            "step limit of 10 reached"
+        );
+    }
+
+    #[test]
+    fn code_ranges_skip_data() {
+        let program = crate::make_program(
+            "
+            inp
+            sto x
+            bra next
+            one: dat 1
+            next: out
+            hlt
+            x var",
+        )
+        .unwrap();
+
+        assert_eq!(code_ranges(&program.parse_info), [0..3, 4..6]);
+    }
+
+    #[test]
+    fn parse_mem_values() {
+        let mem = parse_mem("901 902\n  0\n\n999").unwrap();
+        assert_eq!(mem[..5], [901, 902, 0, 999, 0]);
+        assert_eq!(mem[99], 0);
+
+        assert_eq!(parse_mem("").unwrap(), [0; 100]);
+        assert!(parse_mem(&"1 ".repeat(100)).is_ok());
+
+        assert_eq!(
+            parse_mem(&"1 ".repeat(101)).unwrap_err(),
+            "too many values: memory holds 100 cells, found 101"
+        );
+        assert_eq!(
+            parse_mem("901 1000").unwrap_err(),
+            "value 1000 at address 1 is too large"
+        );
+        assert_eq!(
+            parse_mem("901 lda").unwrap_err(),
+            "invalid value \"lda\" at address 1"
+        );
+        assert!(parse_mem("-1").is_err());
+    }
+
+    #[test]
+    fn advisory_for_unread_inputs() {
+        let advisory = |source| input_advisory(&crate::make_program(source).unwrap().parse_info);
+
+        let advisory_msg = advisory(
+            "
+            out
+            hlt
+            .no_inputs [] [0]
+            .inputs [1, 2] [0]",
+        );
+        assert!(advisory_msg.unwrap().ends_with(": inputs"));
+
+        assert!(advisory("out\nhlt\n.no_inputs [] [0]").is_none());
+        assert!(advisory("inp\nout\nhlt\n.inputs [1] [1]").is_none());
+        assert!(advisory("inp 0\nout\nhlt\n.inputs [1] [1]").is_none());
+    }
+
+    #[test]
+    fn mem_grid_format() {
+        let mut mem = [0; 100];
+        mem[0] = 901;
+        mem[9] = 7;
+        mem[10] = 42;
+        mem[99] = 999;
+
+        let grid = format_mem_grid(&mem);
+        let lines: Vec<&str> = grid.lines().collect();
+
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "00: 901 000 000 000 000 000 000 000 000 007");
+        assert_eq!(lines[1], "10: 042 000 000 000 000 000 000 000 000 000");
+        assert_eq!(lines[9], "90: 000 000 000 000 000 000 000 000 000 999");
+    }
+
+    #[test]
+    fn mem_grid_format_extended() {
+        let mut mem = [0; 1000];
+        mem[0] = 9001;
+        mem[999] = 42;
+
+        let grid = format_mem_grid(&mem);
+        let lines: Vec<&str> = grid.lines().collect();
+
+        assert_eq!(lines.len(), 100);
+        assert_eq!(
+            lines[0],
+            "000: 9001 0000 0000 0000 0000 0000 0000 0000 0000 0000"
+        );
+        assert_eq!(
+            lines[99],
+            "990: 0000 0000 0000 0000 0000 0000 0000 0000 0000 0042"
+        );
+    }
+
+    fn run_all(source: &str) -> Vec<LNCTestInfo> {
+        let program = crate::make_program(source).unwrap();
+
+        program
+            .parse_info
+            .tests
+            .iter()
+            .map(|test| {
+                run_test(program.mem, test, BrpMode::default(), false)
+                    .unwrap()
+                    .0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn junit_report() {
+        let (xml, passed) = run_tests_junit(
+            "
+            inp
+            out
+            hlt
+            .echo [1] [1]
+            .wrong [1] [2]
+            .later [1] [1] skip",
+        )
+        .unwrap();
+
+        assert!(!passed);
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="lnc" tests="3" failures="1" skipped="1">
+  <testcase name="echo"/>
+  <testcase name="wrong">
+    <failure message="incorrect outputs">input: [1], expected: [2], actual: [1]</failure>
+  </testcase>
+  <testcase name="later">
+    <skipped/>
+  </testcase>
+</testsuite>
+"#
+        );
+    }
+
+    #[test]
+    fn junit_passes_with_skipped_tests() {
+        let (_, passed) =
+            run_tests_junit("inp\nout\nhlt\n.echo [1] [1]\n.later [1] [2] skip").unwrap();
+        assert!(passed);
+    }
+
+    #[test]
+    fn junit_escapes_names_and_messages() {
+        let test = LNCTest {
+            name: "a<b> & \"c\"".into(),
+            inputs: vec![],
+            outputs: vec![],
+            expect_error: false,
+            mem_checks: vec![],
+            skipped: false,
+        };
+        let info = LNCTestInfo::new(&test, &[], 0, TestResult::Failed("'x' < 'y'".into()));
+
+        let xml = junit_xml(&[info]);
+        assert!(xml.contains(r#"<testsuite name="lnc" tests="1" failures="1" skipped="0">"#));
+        assert!(xml.contains(r#"<testcase name="a&lt;b&gt; &amp; &quot;c&quot;">"#));
+        assert!(xml.contains(r#"<failure message="&apos;x&apos; &lt; &apos;y&apos;">"#));
+    }
+
+    #[test]
+    fn diffs_memory_of_self_modifying_program() {
+        // doubles `add two` (108) into `sub 16` (216) before running it, so it outputs 1
+        let program = crate::make_program(
+            "
+            lda op
+            add op
+            sto op
+            lda one
+            op: add two
+            out
+            hlt
+            one: dat 1
+            two: dat 2
+            .test [] [3]",
+        )
+        .unwrap();
+
+        let (info, final_mem) = run_test(
+            program.mem,
+            &program.parse_info.tests[0],
+            BrpMode::default(),
+            false,
+        )
+        .unwrap();
+        assert!(matches!(info.result, TestResult::Failed(_)));
+
+        assert_eq!(mem_diff(&program.mem, &final_mem), [(4, 108, 216)]);
+        assert_eq!(format_mem_diff(&program.mem, &final_mem), "04: 108 -> 216");
+        assert_eq!(
+            format_mem_diff(&program.mem, &program.mem),
+            "no cells changed"
+        );
+    }
+
+    #[test]
+    fn expected_errors() {
+        let results = run_all(
+            "
+            inp
+            out
+            hlt
+            .errors [] !
+            .no_error [1] !
+            .normal [1] [1]
+            .normal_fails [] [1]",
+        );
+
+        assert!(matches!(results[0].result, TestResult::Passed));
+        assert!(matches!(results[1].result, TestResult::Failed(_)));
+        assert!(matches!(results[2].result, TestResult::Passed));
+        assert!(matches!(results[3].result, TestResult::Failed(_)));
+    }
+
+    #[test]
+    fn reading_too_many_inputs() {
+        let results = run_all(
+            "
+            inp
+            inp
+            inp
+            hlt
+            .over_reads [1, 2] []
+            .no_inputs [] []",
+        );
+
+        assert_eq!(
+            results[0].result.to_string(),
+            "failed: program requested more inputs than provided (2 available)"
+        );
+        assert_eq!(
+            results[1].result.to_string(),
+            "failed: program requested more inputs than provided (0 available)"
+        );
+    }
+
+    #[test]
+    fn memory_assertions() {
+        let results = run_all(
+            "
+            inp
+            sto total
+            add total
+            sto total
+            hlt
+            total: dat 0
+            .doubles [3] [] mem[5]=6
+            .wrong_total [3] [] mem[5]=3
+            .checks_each [2] [] mem[5]=4 mem[0]=902",
+        );
+
+        assert!(matches!(results[0].result, TestResult::Passed));
+        assert_eq!(
+            results[1].result.to_string(),
+            "failed: mem[5] is 6, expected 3"
+        );
+        assert_eq!(
+            results[2].result.to_string(),
+            "failed: mem[0] is 901, expected 902"
+        );
+    }
+
+    #[test]
+    fn infinite_loop_times_out() {
+        let results = run_all(
+            "
+            loop: bra loop
+            .hangs [] []
+            .hangs_expecting_error [] !",
+        );
+
+        for info in &results {
+            assert!(matches!(info.result, TestResult::TimedOut(MAX_STEPS)));
+            assert_eq!(info.ins_count, MAX_STEPS);
+        }
+        assert_eq!(
+            results[0].result.to_string(),
+            format!("failed: timed out after {MAX_STEPS} instructions (possible infinite loop)")
+        );
+    }
+
+    #[test]
+    fn counts_passes_and_failures() {
+        let results = run_all(
+            "
+            inp
+            out
+            hlt
+            .passes [1] [1]
+            .fails [1] [2]
+            .errors [] !
+            .also_passes [5] [5]",
+        );
+        assert_eq!(count_results(&results), (3, 1, 0));
+
+        let hangs = run_all("loop: bra loop\n.hangs [] []");
+        assert_eq!(count_results(&hangs), (0, 1, 0));
+        assert_eq!(count_results(&[]), (0, 0, 0));
+    }
+
+    #[test]
+    fn error_table_shows_address() {
+        let e = LNCError::Runtime {
+            pc: 3,
+            msg: "undefined instruction 400".into(),
+        };
+        let table = make_error_table(&e, TableStyle::Ascii);
+        let rows: Vec<&str> = table.lines().collect();
+
+        assert!(rows[1].contains("address") && rows[1].contains("error"));
+        assert!(rows[3].contains("| 3 ") && rows[3].contains("undefined instruction 400"));
+    }
+
+    #[test]
+    fn duplicate_test_name_in_tests_file() {
+        let res = run_tests_with_file(
+            "hlt\n.halts []",
+            ".other []\n.halts []",
+            None,
+            BrpMode::default(),
+            TableStyle::Sharp,
+            false,
+        );
+
+        assert_eq!(
+            res,
+            Err("in tests file:\nduplicate test name 'halts' (also defined in the program)".into())
+        );
+    }
+
+    #[test]
+    fn skipped_tests_are_not_run() {
+        let results = run_all(
+            "
+            inp
+            out
+            hlt
+            .passes [1] [1]
+            .would_fail [1] [2] skip
+            .would_hang [] [] skip",
+        );
+
+        assert!(matches!(results[1].result, TestResult::Skipped));
+        assert_eq!(results[1].result.to_string(), "skipped");
+        assert_eq!(results[1].actual_output, "-");
+        assert_eq!(results[2].ins_count, 0);
+        assert_eq!(count_results(&results), (1, 0, 2));
+    }
+
+    #[test]
+    fn filter_tests_by_name() {
+        let tests = get_tests(
+            "
+            hlt
+            .zero [] []
+            .one [] []
+            .twenty_one [] []",
+        );
+
+        let names = |filter| -> Vec<&str> {
+            filter_tests(&tests, filter)
+                .iter()
+                .map(|t| t.name.as_str())
+                .collect()
+        };
+
+        assert_eq!(names(None), vec!["zero", "one", "twenty_one"]);
+        assert_eq!(names(Some("zero")), vec!["zero"]);
+        assert_eq!(names(Some("one")), vec!["one", "twenty_one"]);
+        assert!(names(Some("two")).is_empty());
+    }
 }
@@ -0,0 +1,101 @@
+use crate::parse::{Address, Instruction};
+
+pub fn mnemonic(code: usize) -> String {
+    let first_digit = code / 100;
+    let op = code % 100;
+
+    match first_digit {
+        5 => format!("lda {:02}", op),
+        3 => format!("sto {:02}", op),
+        1 => format!("add {:02}", op),
+        2 => format!("sub {:02}", op),
+        9 => match (op / 10, op % 10) {
+            (0, 0) => "nop".to_owned(),
+            (0, 1) => "inp".to_owned(),
+            (0, 2) => "out".to_owned(),
+            (device, 1) => format!("inp {device}"),
+            (device, 2) => format!("out {device}"),
+            _ => "".to_owned(),
+        },
+        0 => {
+            if op == 0 {
+                "hlt".to_owned()
+            } else {
+                "".to_owned()
+            }
+        }
+        7 => format!("brz {:02}", op),
+        8 => format!("brp {:02}", op),
+        6 => format!("bra {:02}", op),
+        _ => "".to_owned(),
+    }
+}
+
+// The inverse of assembler::get_code. Codes that are not a valid instruction
+// decode to `Data`.
+pub fn decode(code: usize) -> Instruction {
+    let (first_digit, op) = (code / 100, code % 100);
+    let addr = Address::Numeric(op);
+
+    match first_digit {
+        5 => Instruction::Load(addr),
+        3 => Instruction::Store(addr),
+        1 => Instruction::Add(addr),
+        2 => Instruction::Subtract(addr),
+        9 => match (op / 10, op % 10) {
+            (0, 0) => Instruction::Nop,
+            (0, 1) => Instruction::Input,
+            (0, 2) => Instruction::Output,
+            (device, 1) => Instruction::InputDevice(device),
+            (device, 2) => Instruction::OutputDevice(device),
+            _ => Instruction::Data(code),
+        },
+        0 if op == 0 => Instruction::Halt,
+        7 => Instruction::BranchZero(addr),
+        8 => Instruction::BranchPositive(addr),
+        6 => Instruction::BranchAlways(addr),
+        _ => Instruction::Data(code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassemble_codes() {
+        assert_eq!(mnemonic(501), "lda 01");
+        assert_eq!(mnemonic(302), "sto 02");
+        assert_eq!(mnemonic(103), "add 03");
+        assert_eq!(mnemonic(204), "sub 04");
+        assert_eq!(mnemonic(900), "nop");
+        assert_eq!(mnemonic(901), "inp");
+        assert_eq!(mnemonic(902), "out");
+        assert_eq!(mnemonic(931), "inp 3");
+        assert_eq!(mnemonic(992), "out 9");
+        assert_eq!(mnemonic(0), "hlt");
+        assert_eq!(mnemonic(799), "brz 99");
+        assert_eq!(mnemonic(898), "brp 98");
+        assert_eq!(mnemonic(697), "bra 97");
+    }
+
+    #[test]
+    fn disassemble_undefined() {
+        assert_eq!(mnemonic(1), "");
+        assert_eq!(mnemonic(903), "");
+        assert_eq!(mnemonic(910), "");
+        assert_eq!(mnemonic(400), "");
+    }
+
+    #[test]
+    fn decode_codes() {
+        assert_eq!(decode(512), Instruction::Load(Address::Numeric(12)));
+        assert_eq!(decode(901), Instruction::Input);
+        assert_eq!(decode(902), Instruction::Output);
+        assert_eq!(decode(942), Instruction::OutputDevice(4));
+        assert_eq!(decode(0), Instruction::Halt);
+        assert_eq!(decode(1), Instruction::Data(1));
+        assert_eq!(decode(400), Instruction::Data(400));
+        assert_eq!(decode(903), Instruction::Data(903));
+    }
+}
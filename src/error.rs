@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LNCError {
+    Lex {
+        line: usize,
+        col: usize,
+        msg: String,
+    },
+    Parse {
+        line: usize,
+        msg: String,
+    },
+    Assemble {
+        msg: String,
+    },
+    Runtime {
+        pc: usize,
+        msg: String,
+    },
+    StepLimit {
+        max_steps: usize,
+    },
+    Multiple(Vec<LNCError>),
+}
+
+impl LNCError {
+    // Collapses a list of errors into one, flattening nested lists so that
+    // `Multiple` never contains another `Multiple`.
+    pub fn from_vec(errors: Vec<LNCError>) -> LNCError {
+        let mut flat = vec![];
+
+        for e in errors {
+            match e {
+                LNCError::Multiple(inner) => flat.extend(inner),
+                e => flat.push(e),
+            }
+        }
+
+        if flat.len() == 1 {
+            flat.remove(0)
+        } else {
+            LNCError::Multiple(flat)
+        }
+    }
+
+    pub fn errors(&self) -> &[LNCError] {
+        match self {
+            LNCError::Multiple(errors) => errors,
+            e => std::slice::from_ref(e),
+        }
+    }
+}
+
+impl fmt::Display for LNCError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LNCError::Lex { line, col, msg } => write!(f, "error @ line {line}:{col}: {msg}"),
+            LNCError::Parse { line, msg } => write!(f, "error @ line {line}: {msg}"),
+            LNCError::Assemble { msg } | LNCError::Runtime { msg, .. } => write!(f, "{msg}"),
+            LNCError::StepLimit { max_steps } => write!(
+                f,
+                "step limit reached: {max_steps} instructions executed without halting"
+            ),
+            LNCError::Multiple(errors) => {
+                for (i, e) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{e}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for LNCError {}
+
+impl From<LNCError> for String {
+    fn from(e: LNCError) -> String {
+        e.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_vec_flattens() {
+        let lex = LNCError::Lex {
+            line: 1,
+            col: 3,
+            msg: "unexpected character '?'".into(),
+        };
+        let asm = LNCError::Assemble {
+            msg: "Label 'x' is not defined".into(),
+        };
+
+        assert_eq!(LNCError::from_vec(vec![lex.clone()]), lex);
+
+        let err = LNCError::from_vec(vec![
+            LNCError::Multiple(vec![lex.clone(), lex.clone()]),
+            asm.clone(),
+        ]);
+        assert_eq!(err.errors(), [lex.clone(), lex, asm]);
+        assert_eq!(
+            err.to_string(),
+            "error @ line 1:3: unexpected character '?'\n\
+             error @ line 1:3: unexpected character '?'\n\
+             Label 'x' is not defined"
+        );
+    }
+}
@@ -1,4 +1,7 @@
 use std::fmt;
+use std::ops::Range;
+
+use crate::LNCError;
 
 pub trait Output {
     fn send(&mut self, val: usize);
@@ -10,6 +13,81 @@ pub trait Input {
 
 pub trait Log {
     fn log(&mut self, msg: String);
+
+    // Loggers that throw the messages away should return false, so the
+    // interpreter can skip formatting them. Building a message for every
+    // instruction was most of the cost of a long run. This only gates the
+    // per-instruction messages sent to `log`: `watch` and `warn` are for
+    // diagnostics that were asked for, and are always sent.
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    // called after each successfully executed instruction
+    fn trace(&mut self, _entry: &TraceEntry) {}
+
+    // a plain-English description of each executed instruction, only sent when
+    // the interpreter was built `with_explanations`
+    fn explain(&mut self, _msg: String) {}
+
+    // a write to a watched address, sent even when `enabled` is false
+    fn watch(&mut self, msg: String) {
+        self.log(msg);
+    }
+
+    // a warning from an opt-in check, also sent even when `enabled` is false
+    fn warn(&mut self, msg: String) {
+        self.log(msg);
+    }
+}
+
+// Boxed handlers, so the I/O can be chosen at runtime with `Box<dyn Input>`
+// and friends.
+impl<T: Output + ?Sized> Output for Box<T> {
+    fn send(&mut self, val: usize) {
+        (**self).send(val);
+    }
+}
+
+impl<T: Input + ?Sized> Input for Box<T> {
+    fn take(&mut self) -> Result<LNCInput, String> {
+        (**self).take()
+    }
+}
+
+impl<T: Log + ?Sized> Log for Box<T> {
+    fn log(&mut self, msg: String) {
+        (**self).log(msg);
+    }
+
+    fn enabled(&self) -> bool {
+        (**self).enabled()
+    }
+
+    fn trace(&mut self, entry: &TraceEntry) {
+        (**self).trace(entry);
+    }
+
+    fn explain(&mut self, msg: String) {
+        (**self).explain(msg);
+    }
+
+    fn watch(&mut self, msg: String) {
+        (**self).watch(msg);
+    }
+
+    fn warn(&mut self, msg: String) {
+        (**self).warn(msg);
+    }
+}
+
+pub struct TraceEntry {
+    pub ins: usize,
+    pub pc: usize,
+    pub code: usize,
+    pub acc_before: usize,
+    pub acc_after: usize,
+    pub neg_flag: bool,
 }
 
 #[derive(Clone)]
@@ -29,7 +107,12 @@ impl fmt::Display for LNCInput {
 
 impl LNCInput {
     pub fn new(num: usize) -> Option<Self> {
-        if num < 1000 {
+        Self::with_limit(num, 1000)
+    }
+
+    // for wider words than classic LMC's, e.g. with `MemSize::Extended`
+    pub fn with_limit(num: usize, limit: usize) -> Option<Self> {
+        if num < limit {
             Some(LNCInput(num))
         } else {
             None
@@ -43,40 +126,245 @@ impl From<LNCInput> for usize {
     }
 }
 
-pub struct Interpreter<'a, I: Input, O: Output, L: Log> {
-    mem: [usize; 100],
+/// When `brp` takes its branch. Classic LMC branches on zero or positive, some
+/// textbooks only on strictly positive values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BrpMode {
+    #[default]
+    ZeroOrPositive,
+    Positive,
+}
+
+/// How much memory the machine has. Classic LMC has 100 cells of three-digit
+/// words. The extended model has 1000 cells of four-digit words: the first digit
+/// is still the opcode and the other three are the address, so `lda 512` is
+/// 5512, `inp` is 9001 and the accumulator holds 0-9999.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MemSize {
+    #[default]
+    #[value(name = "100")]
+    Classic,
+    #[value(name = "1000")]
+    Extended,
+}
+
+impl MemSize {
+    pub fn cells(self) -> usize {
+        match self {
+            MemSize::Classic => 100,
+            MemSize::Extended => 1000,
+        }
+    }
+}
+
+/// Why `run_until_stopped` stopped.
+#[derive(Debug, PartialEq, Eq)]
+pub enum HaltReason {
+    /// executed the `hlt` at `addr`
+    Halted {
+        addr: usize,
+    },
+    /// executed `max_steps` instructions without halting
+    StepLimit {
+        max_steps: usize,
+    },
+    Error(LNCError),
+}
+
+// `N` is the number of memory cells, see `MemSize`.
+pub struct Interpreter<
+    'a,
+    I: Input + ?Sized,
+    O: Output + ?Sized,
+    L: Log + ?Sized,
+    const N: usize = 100,
+> {
+    mem: [usize; N],
+    // what `reset` puts back into memory
+    initial: [usize; N],
     pc: usize,
     acc: usize,
     neg_flag: bool,
     halted: bool,
+    ins_count: usize,
+    overflow_count: usize,
+    underflow_count: usize,
+    max_acc: usize,
+    opcode_counts: [usize; 10],
+    // how many times the instruction at each address ran, only when profiling
+    profile: Option<[usize; N]>,
+    brp_mode: BrpMode,
+    protected: Vec<Range<usize>>,
+    // which cells have been written, only tracked when uninitialised reads are errors
+    written: Option<[bool; N]>,
+    initialised: Option<[bool; N]>,
+    explain: bool,
+    // branch targets at or past this are warned about, once per branch
+    program_len: Option<usize>,
+    warned_branches: [bool; N],
+    // every `sto` into these addresses is logged
+    watched: Vec<usize>,
     input: &'a mut I,
     output: &'a mut O,
     logger: &'a mut L,
 }
 
-pub struct InterpreterState {
-    pub mem: [usize; 100],
+#[derive(Clone)]
+pub struct InterpreterState<const N: usize = 100> {
+    pub mem: [usize; N],
     pub pc: usize,
     pub acc: usize,
     pub neg_flag: bool,
     pub halted: bool,
 }
 
-impl<'a, I: Input, O: Output, L: Log> Interpreter<'a, I, O, L> {
-    pub fn new(mem: [usize; 100], input: &'a mut I, output: &'a mut O, logger: &'a mut L) -> Self {
+impl<const N: usize> InterpreterState<N> {
+    pub fn new(mem: [usize; N]) -> Self {
         Self {
             mem,
             pc: 0,
             acc: 0,
             neg_flag: false,
             halted: false,
+        }
+    }
+}
+
+impl<'a, I: Input + ?Sized, O: Output + ?Sized, L: Log + ?Sized, const N: usize>
+    Interpreter<'a, I, O, L, N>
+{
+    // one past the largest value a cell or the accumulator can hold
+    const WORD: usize = N * 10;
+    const DIGITS: usize = Self::WORD.ilog10() as usize;
+
+    pub fn new(mem: [usize; N], input: &'a mut I, output: &'a mut O, logger: &'a mut L) -> Self {
+        Self::from_state(InterpreterState::new(mem), input, output, logger)
+    }
+
+    pub fn from_state(
+        state: InterpreterState<N>,
+        input: &'a mut I,
+        output: &'a mut O,
+        logger: &'a mut L,
+    ) -> Self {
+        Self {
+            mem: state.mem,
+            initial: state.mem,
+            pc: state.pc,
+            acc: state.acc,
+            neg_flag: state.neg_flag,
+            halted: state.halted,
+            ins_count: 0,
+            overflow_count: 0,
+            underflow_count: 0,
+            max_acc: state.acc,
+            opcode_counts: [0; 10],
+            profile: None,
+            brp_mode: BrpMode::default(),
+            protected: vec![],
+            written: None,
+            initialised: None,
+            explain: false,
+            program_len: None,
+            warned_branches: [false; N],
+            watched: vec![],
             input,
             output,
             logger,
         }
     }
 
-    pub fn state(&self) -> InterpreterState {
+    /// Puts memory back to how it was when the interpreter was made (or the
+    /// last `load_program`) and clears the registers and counters.
+    pub fn reset(&mut self) {
+        self.mem = self.initial;
+        self.pc = 0;
+        self.acc = 0;
+        self.neg_flag = false;
+        self.halted = false;
+        self.ins_count = 0;
+        self.overflow_count = 0;
+        self.underflow_count = 0;
+        self.max_acc = 0;
+        self.opcode_counts = [0; 10];
+        if let Some(profile) = &mut self.profile {
+            *profile = [0; N];
+        }
+        self.written = self.initialised;
+        self.warned_branches = [false; N];
+    }
+
+    /// Replaces the program, keeping the I/O handlers, and resets. Settings
+    /// that describe the old program (protected ranges, uninitialised and
+    /// branch checks) are dropped.
+    pub fn load_program(&mut self, mem: [usize; N]) {
+        self.initial = mem;
+        self.protected.clear();
+        self.initialised = None;
+        self.program_len = None;
+        self.reset();
+    }
+
+    /// Writes `val` into memory cell `addr`, as if it had been stored there.
+    pub fn set_mem(&mut self, addr: usize, val: usize) -> Result<(), String> {
+        if addr >= N {
+            return Err(format!("invalid address {addr}: must be 0-{}", N - 1));
+        }
+        if val >= Self::WORD {
+            return Err(format!("invalid value {val}: must be 0-{}", Self::WORD - 1));
+        }
+
+        self.mem[addr] = val;
+        if let Some(written) = &mut self.written {
+            written[addr] = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn with_brp_mode(mut self, brp_mode: BrpMode) -> Self {
+        self.brp_mode = brp_mode;
+        self
+    }
+
+    /// Makes `sto` into any address in `range` a runtime error.
+    pub fn protect(&mut self, range: Range<usize>) {
+        self.protected.push(range);
+    }
+
+    /// Makes `lda`, `add` and `sub` from a cell that is neither in `initialised`
+    /// nor has been the target of a `sto` a runtime error.
+    pub fn with_uninit_checks(mut self, initialised: [bool; N]) -> Self {
+        self.written = Some(initialised);
+        self.initialised = Some(initialised);
+        self
+    }
+
+    /// Counts how many times each address is executed, see `profile`.
+    pub fn with_profiling(mut self) -> Self {
+        self.profile = Some([0; N]);
+        self
+    }
+
+    /// Logs each `sto` into one of `addrs`, with the old and new values.
+    pub fn with_watched_addrs(mut self, addrs: &[usize]) -> Self {
+        self.watched = addrs.to_vec();
+        self
+    }
+
+    pub fn with_explanations(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+
+    /// Logs a warning the first time each branch jumps to an address at or
+    /// past `len`, where memory usually holds nothing but `hlt`s.
+    pub fn with_branch_checks(mut self, len: usize) -> Self {
+        self.program_len = Some(len);
+        self
+    }
+
+    pub fn state(&self) -> InterpreterState<N> {
         InterpreterState {
             mem: self.mem,
             pc: self.pc,
@@ -86,49 +374,200 @@ impl<'a, I: Input, O: Output, L: Log> Interpreter<'a, I, O, L> {
         }
     }
 
+    pub fn input(&self) -> &I {
+        self.input
+    }
+
+    pub fn output(&self) -> &O {
+        self.output
+    }
+
     pub fn is_halted(&self) -> bool {
         self.halted
     }
 
-    pub fn step(&mut self) -> Result<(), String> {
+    /// Total number of instructions executed since the interpreter was created.
+    pub fn ins_count(&self) -> usize {
+        self.ins_count
+    }
+
+    /// Number of times `add` went past 999 and wrapped around.
+    pub fn overflow_count(&self) -> usize {
+        self.overflow_count
+    }
+
+    /// Number of times `sub` went below 0 and wrapped around.
+    pub fn underflow_count(&self) -> usize {
+        self.underflow_count
+    }
+
+    /// Largest value the accumulator has held.
+    pub fn max_acc(&self) -> usize {
+        self.max_acc
+    }
+
+    /// Number of instructions executed with each opcode (the first digit), so
+    /// `[5]` counts `lda` and `[9]` counts `inp`, `out` and `nop` together.
+    pub fn opcode_counts(&self) -> [usize; 10] {
+        self.opcode_counts
+    }
+
+    /// Number of times the instruction at each address was executed. All zero
+    /// unless the interpreter was built `with_profiling`.
+    pub fn profile(&self) -> [usize; N] {
+        self.profile.unwrap_or([0; N])
+    }
+
+    /// Executes up to `n` instructions, stopping early if the program halts.
+    /// Returns the number of instructions executed.
+    pub fn step_n(&mut self, n: usize) -> Result<usize, LNCError> {
+        let mut count = 0;
+
+        while count < n && !self.halted {
+            self.step()?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Steps until the program halts, returning the number of instructions
+    /// executed by this call. Errors if `max_steps` instructions are executed
+    /// without halting.
+    pub fn run_to_halt(&mut self, max_steps: usize) -> Result<usize, LNCError> {
+        let mut count = 0;
+
+        while !self.halted {
+            if count >= max_steps {
+                return Err(LNCError::StepLimit { max_steps });
+            }
+
+            self.step()?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Like `run_to_halt`, but calls `on_step` with the state after each
+    /// instruction, e.g. so a frontend can redraw.
+    pub fn run_with_callback<F: FnMut(&InterpreterState<N>)>(
+        &mut self,
+        max_steps: usize,
+        mut on_step: F,
+    ) -> Result<usize, LNCError> {
+        let mut count = 0;
+
+        while !self.halted {
+            if count >= max_steps {
+                return Err(LNCError::StepLimit { max_steps });
+            }
+
+            self.step()?;
+            count += 1;
+            on_step(&self.state());
+        }
+
+        Ok(count)
+    }
+
+    /// Like `run_to_halt`, but says why the program stopped.
+    pub fn run_until_stopped(&mut self, max_steps: usize) -> HaltReason {
+        match self.run_to_halt(max_steps) {
+            // the pc has already moved past the `hlt`, unless the interpreter
+            // was built from a state that was halted at address 0
+            Ok(_) => HaltReason::Halted {
+                addr: self.pc.saturating_sub(1),
+            },
+            Err(LNCError::StepLimit { max_steps }) => HaltReason::StepLimit { max_steps },
+            Err(e) => HaltReason::Error(e),
+        }
+    }
+
+    pub fn step(&mut self) -> Result<(), LNCError> {
         if self.halted {
-            self.logger.log("Cannot step: interpreter is halted".into());
+            self.log(|_| "Cannot step: interpreter is halted".into());
             return Ok(());
         }
 
+        // only reachable by executing something other than a branch at address 99
+        if self.pc >= self.mem.len() {
+            return Err(LNCError::Runtime {
+                pc: self.pc - 1,
+                msg: format!(
+                    "error at address {}: ran past the end of memory",
+                    self.pc - 1
+                ),
+            });
+        }
+
+        let (pc, acc_before) = (self.pc, self.acc);
         let code = self.mem[self.pc];
 
-        self.logger.log(format!(
-            "Fetched instruction: {} at address {}",
-            code, self.pc
-        ));
+        self.log(|s| format!("Fetched instruction: {} at address {}", code, s.pc));
 
         self.pc += 1;
 
-        let (first_digit, op) = (code / 100, code % 100);
+        if let Err(msg) = self.execute(code) {
+            return Err(LNCError::Runtime { pc, msg });
+        }
+
+        self.max_acc = self.max_acc.max(self.acc);
+
+        if self.explain {
+            let msg = self.describe(code, acc_before);
+            self.logger.explain(msg);
+        }
+
+        self.logger.trace(&TraceEntry {
+            ins: self.ins_count,
+            pc,
+            code,
+            acc_before,
+            acc_after: self.acc,
+            neg_flag: self.neg_flag,
+        });
+
+        self.ins_count += 1;
+        self.opcode_counts[code / N] += 1;
+        if let Some(profile) = &mut self.profile {
+            profile[pc] += 1;
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, code: usize) -> Result<(), String> {
+        let (first_digit, op) = (code / N, code % N);
 
         match first_digit {
             // load
-            5 => self.lda(op),
+            5 => self.lda(op)?,
             // store
-            3 => self.sto(op),
+            3 => self.sto(op)?,
             // add
-            1 => self.add(op),
+            1 => self.add(op)?,
             // subtract
-            2 => self.sub(op),
+            2 => self.sub(op)?,
+            // the middle digit of 9xx selects a device, only device 0 exists for now
             9 => {
-                match op {
+                match (op / 10, op % 10) {
+                    // no-op
+                    (0, 0) => self.nop(),
                     // input
-                    01 => self.inp()?,
+                    (0, 1) => self.inp()?,
                     // output
-                    02 => self.out(),
-                    _ => return Err(format!("{}{}: undefined instruction", first_digit, op)),
+                    (0, 2) => self.out(),
+                    (device, 1 | 2) => {
+                        return Err(format!("{code}: device {device} is not available"))
+                    }
+                    _ => return Err(self.undefined(code)),
                 }
             }
             // halt
             0 => match op {
-                00 => self.hlt(),
-                _ => return Err(format!("{}{}: undefined instruction", first_digit, op)),
+                0 => self.hlt(),
+                _ => return Err(self.undefined(code)),
             },
             // branch if zero
             7 => self.brz(op),
@@ -136,27 +575,144 @@ impl<'a, I: Input, O: Output, L: Log> Interpreter<'a, I, O, L> {
             8 => self.brp(op),
             // branch always
             6 => self.bra(op),
-            _ => return Err(format!("{}{}: undefined instruction", first_digit, op)),
+            _ => return Err(self.undefined(code)),
         };
 
         Ok(())
     }
 
-    fn lda(&mut self, addr: usize) {
-        self.logger.log(format!("--> lda {}", addr));
-        self.acc = self.mem[addr];
+    fn read(&self, addr: usize) -> Result<usize, String> {
+        match self.written {
+            Some(written) if !written[addr] => {
+                Err(format!("read from uninitialised address {addr}"))
+            }
+            _ => Ok(self.mem[addr]),
+        }
+    }
+
+    // Only builds the message if the logger will use it. Only for the
+    // per-instruction messages, see `Log::enabled`.
+    fn log(&mut self, msg: impl FnOnce(&Self) -> String) {
+        if self.logger.enabled() {
+            let msg = msg(self);
+            self.logger.log(msg);
+        }
     }
 
-    fn sto(&mut self, addr: usize) {
-        self.logger.log(format!("--> sto {}", addr));
+    fn undefined(&self, code: usize) -> String {
+        // the pc has already moved past the instruction
+        format!(
+            "error at address {}: {code:0width$} is an undefined instruction",
+            self.pc - 1,
+            width = Self::DIGITS
+        )
+    }
+
+    // Describes the instruction `code` that was just executed, given the
+    // accumulator beforehand.
+    fn describe(&self, code: usize, acc_before: usize) -> String {
+        let addr = code % N;
+
+        match code / N {
+            5 => format!(
+                "Loaded the value {} from address {addr} into the accumulator.",
+                self.acc
+            ),
+            3 => format!(
+                "Stored the accumulator's value {} into address {addr}.",
+                self.acc
+            ),
+            1 => {
+                let value = self.mem[addr];
+                let wrapped = if acc_before + value >= Self::WORD {
+                    format!(", wrapping around past {}", Self::WORD - 1)
+                } else {
+                    String::new()
+                };
+
+                format!(
+                    "Added the value {value} from address {addr} to {acc_before}, leaving {} in the accumulator{wrapped}.",
+                    self.acc
+                )
+            }
+            2 => {
+                let value = self.mem[addr];
+                let negative = if self.neg_flag {
+                    " The result was negative, so the negative flag is set."
+                } else {
+                    ""
+                };
+
+                format!(
+                    "Subtracted the value {value} at address {addr} from {acc_before}, leaving {} in the accumulator.{negative}",
+                    self.acc
+                )
+            }
+            7 if self.acc == 0 => {
+                format!("The accumulator is 0, so jumped to address {addr}.")
+            }
+            7 => "The accumulator is not 0, so carried on to the next instruction.".into(),
+            8 if self.brp_taken() => {
+                format!("The negative flag is not set, so jumped to address {addr}.")
+            }
+            8 => match self.brp_mode {
+                BrpMode::Positive if !self.neg_flag => {
+                    "The accumulator is 0, so carried on to the next instruction.".into()
+                }
+                _ => "The negative flag is set, so carried on to the next instruction.".into(),
+            },
+            6 => format!("Jumped to address {addr}."),
+            _ => match (code / N, addr) {
+                (0, 0) => "Halted the program.".into(),
+                (9, 0) => "Did nothing.".into(),
+                (9, 1) => format!("Read the input value {} into the accumulator.", self.acc),
+                (9, 2) => format!("Output the accumulator's value {}.", self.acc),
+                _ => format!("Ran {code:0width$}.", width = Self::DIGITS),
+            },
+        }
+    }
+
+    fn lda(&mut self, addr: usize) -> Result<(), String> {
+        self.log(|_| format!("--> lda {}", addr));
+        self.acc = self.read(addr)?;
+
+        Ok(())
+    }
+
+    fn sto(&mut self, addr: usize) -> Result<(), String> {
+        self.log(|_| format!("--> sto {}", addr));
+
+        if self.protected.iter().any(|range| range.contains(&addr)) {
+            return Err(format!("write to protected address {addr}"));
+        }
+
+        if self.watched.contains(&addr) {
+            let msg = format!(
+                "watch @ address {addr}: {} -> {} (sto at address {}, ins #{})",
+                self.mem[addr],
+                self.acc,
+                self.pc - 1,
+                self.ins_count
+            );
+            self.logger.watch(msg);
+        }
+
         self.mem[addr] = self.acc;
+        if let Some(written) = &mut self.written {
+            written[addr] = true;
+        }
+
+        Ok(())
     }
 
     fn inp(&mut self) -> Result<(), String> {
-        self.logger.log("--> inp".into());
+        self.log(|_| "--> inp".into());
 
-        let inp_val = self.input.take()?.into();
-        self.logger.log(format!("--> {} was input value", inp_val));
+        let inp_val: usize = self.input.take()?.into();
+        if inp_val >= Self::WORD {
+            return Err(format!("input value {inp_val} is too large"));
+        }
+        self.log(|_| format!("--> {} was input value", inp_val));
 
         self.acc = inp_val;
 
@@ -164,66 +720,944 @@ impl<'a, I: Input, O: Output, L: Log> Interpreter<'a, I, O, L> {
     }
 
     fn out(&mut self) {
-        self.logger.log("--> out".into());
-        self.logger
-            .log(format!("--> {} was output value", self.acc));
+        self.log(|_| "--> out".into());
+        self.log(|s| format!("--> {} was output value", s.acc));
 
         self.output.send(self.acc);
     }
 
+    fn nop(&mut self) {
+        self.log(|_| "--> nop".into());
+    }
+
     fn hlt(&mut self) {
-        self.logger.log("--> hlt".into());
+        self.log(|_| "--> hlt".into());
         self.halted = true;
     }
 
-    fn add(&mut self, addr: usize) {
-        self.logger.log(format!("--> add {}", addr));
+    fn add(&mut self, addr: usize) -> Result<(), String> {
+        self.log(|_| format!("--> add {}", addr));
 
-        let new_val = self.acc + self.mem[addr];
-        if new_val >= 1000 {
-            self.logger.log(format!(
-                "--> {} + {} = {} >= 1000: overflow",
-                self.acc, self.mem[addr], new_val
-            ));
+        let value = self.read(addr)?;
+        let new_val = self.acc + value;
+        if new_val >= Self::WORD {
+            self.overflow_count += 1;
+            self.log(|s| {
+                format!(
+                    "--> {} + {} = {} >= {}: overflow",
+                    s.acc,
+                    value,
+                    new_val,
+                    Self::WORD
+                )
+            });
         }
-        self.acc = new_val % 1000;
+        self.acc = new_val % Self::WORD;
 
         self.neg_flag = false;
+
+        Ok(())
     }
 
-    fn sub(&mut self, addr: usize) {
-        self.logger.log(format!("--> sub {}", addr));
+    fn sub(&mut self, addr: usize) -> Result<(), String> {
+        self.log(|_| format!("--> sub {}", addr));
 
-        let new_val = self.acc as isize - self.mem[addr] as isize;
+        let value = self.read(addr)?;
+        let new_val = self.acc as isize - value as isize;
         self.neg_flag = new_val < 0;
 
         if self.neg_flag {
-            self.logger.log(format!(
-                "--> {} - {} = {} < 1000: underflow",
-                self.acc, self.mem[addr], new_val
-            ));
-            self.logger.log("neg_flag set".into());
+            self.underflow_count += 1;
+            self.log(|s| format!("--> {} - {} = {} < 0: underflow", s.acc, value, new_val));
+            self.log(|_| "neg_flag set".into());
         }
 
-        self.acc = (new_val + 1000) as usize % 1000;
+        self.acc = (new_val + Self::WORD as isize) as usize % Self::WORD;
+
+        Ok(())
     }
 
     fn brz(&mut self, addr: usize) {
-        self.logger.log(format!("--> brz {}", addr));
+        self.log(|_| format!("--> brz {}", addr));
         if self.acc == 0 {
-            self.pc = addr;
+            self.branch(addr);
         }
     }
 
     fn brp(&mut self, addr: usize) {
-        self.logger.log(format!("--> brp {}", addr));
-        if !self.neg_flag {
-            self.pc = addr;
+        self.log(|_| format!("--> brp {}", addr));
+
+        if self.brp_taken() {
+            self.branch(addr);
+        }
+    }
+
+    fn brp_taken(&self) -> bool {
+        match self.brp_mode {
+            BrpMode::ZeroOrPositive => !self.neg_flag,
+            BrpMode::Positive => !self.neg_flag && self.acc > 0,
         }
     }
 
     fn bra(&mut self, addr: usize) {
-        self.logger.log(format!("--> bra {}", addr));
+        self.log(|_| format!("--> bra {}", addr));
+        self.branch(addr);
+    }
+
+    fn branch(&mut self, addr: usize) {
+        // the pc has already moved past the branch
+        let pc = self.pc - 1;
+
+        if let Some(len) = self.program_len {
+            if addr >= len && !self.warned_branches[pc] {
+                self.warned_branches[pc] = true;
+                self.logger.warn(format!(
+                    "warning @ address {pc}: branch to address {addr}, past the end of the program"
+                ));
+            }
+        }
+
         self.pc = addr;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_mem, run_source};
+    use crate::vec_io::{NullLogger, QueueInput, StackOutput};
+
+    fn make_mem(codes: &[usize]) -> [usize; 100] {
+        let mut mem = [0; 100];
+        mem[..codes.len()].copy_from_slice(codes);
+        mem
+    }
+
+    #[test]
+    fn extended_memory() {
+        // inp, sto 500, add 500, sto 999, out, bra 998 ... 998: hlt
+        let mut mem = [0; 1000];
+        mem[..6].copy_from_slice(&[9001, 3500, 1500, 3999, 9002, 6998]);
+
+        let mut input = QueueInput::new(&[600]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
+        assert_eq!(interpreter.run_to_halt(100), Ok(7));
+
+        let state = interpreter.state();
+        assert_eq!(state.mem[500], 600);
+        assert_eq!(state.mem[999], 1200);
+        assert_eq!(state.pc, 999);
+        assert_eq!(output.stack, [1200]);
+    }
+
+    #[test]
+    fn extended_memory_wraps_at_four_digits() {
+        // lda 10, add 10, sub 11, hlt, then an undefined instruction
+        let mut mem = [0; 1000];
+        mem[..4].copy_from_slice(&[5010, 1010, 2011, 0]);
+        mem[10] = 6000;
+        mem[11] = 9999;
+        mem[20] = 4000;
+
+        let mut input = QueueInput::default();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
+        interpreter.run_to_halt(100).unwrap();
+
+        // 6000 + 6000 wraps to 2000, then 2000 - 9999 wraps to 2001
+        assert_eq!(interpreter.state().acc, 2001);
+        assert!(interpreter.state().neg_flag);
+        assert_eq!(interpreter.overflow_count(), 1);
+        assert_eq!(interpreter.underflow_count(), 1);
+
+        let mut interpreter = Interpreter::from_state(
+            InterpreterState {
+                pc: 20,
+                ..InterpreterState::new(mem)
+            },
+            &mut input,
+            &mut output,
+            &mut logger,
+        );
+        assert_eq!(
+            interpreter.step(),
+            Err(LNCError::Runtime {
+                pc: 20,
+                msg: "error at address 20: 4000 is an undefined instruction".into()
+            })
+        );
+    }
+
+    #[test]
+    fn run_to_halt_counts_instructions() {
+        // inp, out, hlt
+        let run = run_mem(make_mem(&[901, 902, 0]), &[7], |i| i);
+
+        assert_eq!(run.result, Ok(3));
+        assert!(run.state.halted);
+        assert_eq!(run.ins_count, 3);
+        assert_eq!(run.outputs, vec![7]);
+    }
+
+    #[test]
+    fn io_visible_while_running() {
+        // inp, out, hlt
+        let mem = make_mem(&[901, 902, 0]);
+
+        let mut input = QueueInput::new(&[4]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
+
+        interpreter.step().unwrap();
+        assert!(interpreter.input().queue.is_empty());
+        assert!(interpreter.output().stack.is_empty());
+
+        interpreter.step().unwrap();
+        assert_eq!(interpreter.output().stack, vec![4]);
+    }
+
+    #[test]
+    fn unavailable_device() {
+        // inp 1
+        let run = run_mem(make_mem(&[911]), &[1], |i| i);
+
+        assert_eq!(
+            run.result,
+            Err(LNCError::Runtime {
+                pc: 0,
+                msg: "911: device 1 is not available".into()
+            })
+        );
+    }
+
+    #[test]
+    fn boxed_io() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        struct SharedOutput(Rc<RefCell<Vec<usize>>>);
+
+        impl Output for SharedOutput {
+            fn send(&mut self, val: usize) {
+                self.0.borrow_mut().push(val);
+            }
+        }
+
+        // inp, out, inp, out, hlt
+        let mem = make_mem(&[901, 902, 901, 902, 0]);
+        let outputs = Rc::new(RefCell::new(vec![]));
+
+        let mut input: Box<dyn Input> = Box::new(QueueInput::new(&[3, 4]).unwrap());
+        let mut output: Box<dyn Output> = Box::new(SharedOutput(Rc::clone(&outputs)));
+        let mut logger: Box<dyn Log> = Box::new(NullLogger);
+
+        let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
+
+        assert_eq!(interpreter.run_to_halt(100), Ok(5));
+        assert_eq!(*outputs.borrow(), vec![3, 4]);
+    }
+
+    #[test]
+    fn nop_does_nothing() {
+        // nop, inp, nop, out, hlt
+        let mem = make_mem(&[900, 901, 900, 902, 0]);
+
+        let mut input = QueueInput::new(&[5]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
+        interpreter.step().unwrap();
+
+        let state = interpreter.state();
+        assert_eq!((state.pc, state.acc, state.neg_flag), (1, 0, false));
+
+        assert_eq!(interpreter.run_to_halt(100), Ok(4));
+        assert_eq!(output.stack, vec![5]);
+    }
+
+    #[test]
+    fn step_n_stops_on_halt() {
+        // inp, out, sub 05, brp 01, hlt, dat 1
+        let mem = make_mem(&[901, 902, 205, 801, 0, 1]);
+
+        let mut input = QueueInput::new(&[2]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
+
+        assert_eq!(interpreter.step_n(0), Ok(0));
+        assert_eq!(interpreter.step_n(4), Ok(4));
+        assert_eq!(interpreter.state().pc, 1);
+        assert!(!interpreter.is_halted());
+
+        assert_eq!(interpreter.step_n(100), Ok(7));
+        assert!(interpreter.is_halted());
+        assert_eq!(interpreter.step_n(5), Ok(0));
+        assert_eq!(output.stack, vec![2, 1, 0]);
+    }
+
+    // outputs the input only if brp does not branch
+    fn brp_outputs(brp_mode: BrpMode, value: usize) -> Vec<usize> {
+        // inp, brp 04, out, hlt, hlt
+        let run = run_mem(make_mem(&[901, 804, 902, 0, 0]), &[value], |i| {
+            i.with_brp_mode(brp_mode)
+        });
+        run.result.unwrap();
+
+        run.outputs
+    }
+
+    #[test]
+    fn brp_zero_or_positive() {
+        assert_eq!(brp_outputs(BrpMode::ZeroOrPositive, 5), vec![]);
+        assert_eq!(brp_outputs(BrpMode::ZeroOrPositive, 0), vec![]);
+    }
+
+    #[test]
+    fn brp_strictly_positive() {
+        assert_eq!(brp_outputs(BrpMode::Positive, 5), vec![]);
+        assert_eq!(brp_outputs(BrpMode::Positive, 0), vec![0]);
+    }
+
+    #[test]
+    fn logs_underflow() {
+        // sub 02, hlt, then 1 as data
+        let run = run_mem(make_mem(&[202, 0, 1]), &[], |i| i);
+        run.result.unwrap();
+
+        assert_eq!(
+            run.logs,
+            [
+                "Fetched instruction: 202 at address 0",
+                "--> sub 2",
+                "--> 0 - 1 = -1 < 0: underflow",
+                "neg_flag set",
+                "Fetched instruction: 0 at address 1",
+                "--> hlt",
+            ]
+        );
+    }
+
+    #[test]
+    fn logs_overflow_and_io() {
+        // inp, add 04, out, hlt, then 600 as data
+        let run = run_mem(make_mem(&[901, 104, 902, 0, 600]), &[400], |i| i);
+        run.result.unwrap();
+
+        let messages = &run.logs;
+        assert!(messages.contains(&"--> 400 was input value".to_owned()));
+        assert!(messages.contains(&"--> 400 + 600 = 1000 >= 1000: overflow".to_owned()));
+        assert!(messages.contains(&"--> 0 was output value".to_owned()));
+        assert!(!messages.contains(&"neg_flag set".to_owned()));
+    }
+
+    #[test]
+    fn protected_writes() {
+        // inp, sto 10, inp, sto 01, hlt
+        let run = run_mem(make_mem(&[901, 310, 901, 301, 0]), &[7, 8], |mut i| {
+            i.protect(0..5);
+            i
+        });
+
+        assert_eq!(
+            run.result,
+            Err(LNCError::Runtime {
+                pc: 3,
+                msg: "write to protected address 1".into()
+            })
+        );
+        assert_eq!(run.state.mem[10], 7);
+        assert_eq!(run.state.mem[1], 310);
+    }
+
+    #[test]
+    fn unprotected_writes() {
+        let run = run_mem(make_mem(&[901, 310, 901, 301, 0]), &[7, 8], |mut i| {
+            i.protect(5..10);
+            i.protect(11..100);
+            i
+        });
+
+        assert_eq!(run.result, Ok(5));
+        assert_eq!(run.state.mem[..2], [901, 8]);
+    }
+
+    fn initialised(len: usize) -> [bool; 100] {
+        let mut initialised = [false; 100];
+        initialised[..len].fill(true);
+        initialised
+    }
+
+    #[test]
+    fn uninitialised_reads() {
+        // lda 50, add 50 and sub 50, each followed by hlt
+        for code in [550, 150, 250] {
+            let run = run_mem(make_mem(&[code, 0]), &[], |i| {
+                i.with_uninit_checks(initialised(2))
+            });
+
+            assert_eq!(
+                run.result,
+                Err(LNCError::Runtime {
+                    pc: 0,
+                    msg: "read from uninitialised address 50".into()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn stored_and_program_cells_are_initialised() {
+        // inp, sto 50, lda 50, add 06, sub 50, out, hlt
+        let mem = make_mem(&[901, 350, 550, 106, 250, 902, 0]);
+        let run = run_mem(mem, &[7], |i| i.with_uninit_checks(initialised(7)));
+
+        assert_eq!(run.result, Ok(7));
+        assert_eq!(run.outputs, [0]);
+    }
+
+    #[test]
+    fn uninitialised_reads_allowed_by_default() {
+        let run = run_mem(make_mem(&[550, 902, 0]), &[], |i| i);
+
+        assert_eq!(run.result, Ok(3));
+        assert_eq!(run.outputs, [0]);
+    }
+
+    fn explanations(source: &str, inputs: &[usize], brp_mode: BrpMode) -> Vec<String> {
+        let program = crate::make_program(source).unwrap();
+
+        let mut input = QueueInput::new(inputs).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = ExplainLogger::default();
+
+        Interpreter::new(program.mem, &mut input, &mut output, &mut logger)
+            .with_brp_mode(brp_mode)
+            .with_explanations(true)
+            .run_to_halt(100)
+            .unwrap();
+
+        logger.0
+    }
+
+    #[derive(Default)]
+    struct ExplainLogger(Vec<String>);
+
+    impl Log for ExplainLogger {
+        fn log(&mut self, _msg: String) {}
+
+        fn explain(&mut self, msg: String) {
+            self.0.push(msg);
+        }
+    }
+
+    #[test]
+    fn explains_each_instruction() {
+        let explained = explanations(
+            "
+            inp
+            sto x
+            add big
+            sub x
+            sub x
+            brp end
+            brz end
+            bra end
+            end: lda x
+            out
+            nop
+            hlt
+            big: dat 999
+            x var",
+            &[7],
+            BrpMode::ZeroOrPositive,
+        );
+
+        assert_eq!(
+            explained,
+            [
+                "Read the input value 7 into the accumulator.",
+                "Stored the accumulator's value 7 into address 13.",
+                "Added the value 999 from address 12 to 7, leaving 6 in the accumulator, wrapping around past 999.",
+                "Subtracted the value 7 at address 13 from 6, leaving 999 in the accumulator. The result was negative, so the negative flag is set.",
+                "Subtracted the value 7 at address 13 from 999, leaving 992 in the accumulator.",
+                "The negative flag is not set, so jumped to address 8.",
+                "Loaded the value 7 from address 13 into the accumulator.",
+                "Output the accumulator's value 7.",
+                "Did nothing.",
+                "Halted the program.",
+            ]
+        );
+    }
+
+    #[test]
+    fn explains_branches_not_taken() {
+        let explained = explanations(
+            "
+            sub one
+            brp 0
+            brz 0
+            lda zero
+            add zero
+            brp 0
+            hlt
+            one: dat 1
+            zero: dat 0",
+            &[],
+            BrpMode::Positive,
+        );
+
+        assert_eq!(
+            explained[1..3],
+            [
+                "The negative flag is set, so carried on to the next instruction.",
+                "The accumulator is not 0, so carried on to the next instruction.",
+            ]
+        );
+        assert_eq!(
+            explained[5],
+            "The accumulator is 0, so carried on to the next instruction."
+        );
+    }
+
+    #[test]
+    fn logs_writes_to_watched_addrs() {
+        // inp, sto 10, add 10, sto 10, sto 11, hlt
+        let mem = make_mem(&[901, 310, 110, 310, 311, 0]);
+        let run = run_mem(mem, &[4], |i| i.with_watched_addrs(&[10, 20]));
+        run.result.unwrap();
+
+        let watches: Vec<&String> = run
+            .logs
+            .iter()
+            .filter(|msg| msg.starts_with("watch"))
+            .collect();
+        assert_eq!(
+            watches,
+            [
+                "watch @ address 10: 0 -> 4 (sto at address 1, ins #1)",
+                "watch @ address 10: 4 -> 8 (sto at address 3, ins #3)",
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_on_branch_past_end() {
+        // a 10 cell program (lda 09, brp 10, ..., 1) with more code after it:
+        // out, sub 09, brz 20 at address 10 and bra 50 at address 20
+        let mut mem = make_mem(&[509, 810, 0, 0, 0, 0, 0, 0, 0, 1]);
+        mem[10..13].copy_from_slice(&[902, 209, 720]);
+        mem[20] = 650;
+
+        let run = run_mem(mem, &[], |i| i.with_branch_checks(10));
+        assert!(run.result.is_ok());
+
+        let warnings: Vec<&String> = run
+            .logs
+            .iter()
+            .filter(|msg| msg.starts_with("warning"))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "warning @ address 1: branch to address 10, past the end of the program",
+                "warning @ address 12: branch to address 20, past the end of the program",
+                "warning @ address 20: branch to address 50, past the end of the program",
+            ]
+        );
+    }
+
+    #[test]
+    fn branch_warnings_once_per_branch() {
+        let run = |len| {
+            // lda 04, sub 05, brp 01, hlt, 3, 1
+            let mem = make_mem(&[504, 205, 801, 0, 3, 1]);
+            let run = run_mem(mem, &[], |i| match len {
+                Some(len) => i.with_branch_checks(len),
+                None => i,
+            });
+            run.result.unwrap();
+
+            run.logs
+                .into_iter()
+                .filter(|msg| msg.starts_with("warning"))
+                .count()
+        };
+
+        assert_eq!(run(Some(6)), 0);
+        // branching back to address 1 is "past" a 1 instruction program
+        assert_eq!(run(Some(1)), 1);
+        assert_eq!(run(None), 0);
+    }
+
+    #[test]
+    fn undefined_instruction_reports_address() {
+        for (code, msg) in [
+            (42, "error at address 2: 042 is an undefined instruction"),
+            (400, "error at address 2: 400 is an undefined instruction"),
+            (903, "error at address 2: 903 is an undefined instruction"),
+        ] {
+            let run = run_mem(make_mem(&[900, 900, code]), &[], |i| i);
+
+            assert_eq!(
+                run.result,
+                Err(LNCError::Runtime {
+                    pc: 2,
+                    msg: msg.into()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn load_a_second_program() {
+        let mut input = QueueInput::new(&[4, 5, 6]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        // inp, sto 10, out, hlt
+        let mut interpreter = Interpreter::new(
+            make_mem(&[901, 310, 902, 0]),
+            &mut input,
+            &mut output,
+            &mut logger,
+        );
+        interpreter.run_to_halt(10).unwrap();
+        assert_eq!(interpreter.state().mem[10], 4);
+
+        // the stored value is gone after a reset
+        interpreter.reset();
+        assert_eq!(interpreter.state().mem[10], 0);
+        assert_eq!(interpreter.ins_count(), 0);
+        interpreter.run_to_halt(10).unwrap();
+        assert_eq!(interpreter.state().mem[10], 5);
+
+        // inp, add 3, out, dat 100
+        interpreter.load_program(make_mem(&[901, 103, 902, 100]));
+        let state = interpreter.state();
+        assert_eq!((state.pc, state.acc, state.halted), (0, 0, false));
+        assert_eq!(state.mem[10], 0);
+        interpreter.step_n(3).unwrap();
+
+        // reset now goes back to the second program
+        interpreter.reset();
+        assert_eq!(interpreter.state().mem[3], 100);
+
+        drop(interpreter);
+        assert_eq!(output.stack, vec![4, 5, 106]);
+    }
+
+    #[test]
+    fn profile_counts_each_address() {
+        let mut input = QueueInput::new(&[3]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        // inp, loop: sub 5, brp loop, hlt, ..., dat 1
+        let mut interpreter = Interpreter::new(
+            make_mem(&[901, 205, 801, 0, 0, 1]),
+            &mut input,
+            &mut output,
+            &mut logger,
+        )
+        .with_profiling();
+        interpreter.run_to_halt(100).unwrap();
+
+        // 2, 1 and 0 branch back, then the fourth subtraction goes negative
+        assert_eq!(interpreter.profile()[..5], [1, 4, 4, 1, 0]);
+
+        interpreter.reset();
+        assert_eq!(interpreter.profile()[..5], [0; 5]);
+    }
+
+    #[test]
+    fn set_mem_patches_a_cell() {
+        let mut input = QueueInput::new(&[]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        // lda 3, out, hlt, dat 0
+        let mut interpreter = Interpreter::new(
+            make_mem(&[503, 902, 0, 0]),
+            &mut input,
+            &mut output,
+            &mut logger,
+        )
+        .with_uninit_checks([false; 100]);
+
+        interpreter.set_mem(3, 123).unwrap();
+        assert_eq!(interpreter.state().mem[3], 123);
+
+        assert_eq!(
+            interpreter.set_mem(100, 1),
+            Err("invalid address 100: must be 0-99".into())
+        );
+        assert_eq!(
+            interpreter.set_mem(3, 1000),
+            Err("invalid value 1000: must be 0-999".into())
+        );
+
+        // the patched cell counts as initialised
+        interpreter.run_to_halt(10).unwrap();
+        drop(interpreter);
+        assert_eq!(output.stack, vec![123]);
+    }
+
+    #[test]
+    fn callback_after_each_step() {
+        let mut input = QueueInput::new(&[7]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        // inp, add 4, out, hlt, dat 3
+        let mut interpreter = Interpreter::new(
+            make_mem(&[901, 104, 902, 0, 3]),
+            &mut input,
+            &mut output,
+            &mut logger,
+        );
+
+        let mut seen = vec![];
+        let count = interpreter
+            .run_with_callback(10, |state| seen.push((state.pc, state.acc)))
+            .unwrap();
+
+        assert_eq!(count, 4);
+        assert_eq!(seen, [(1, 7), (2, 10), (3, 10), (4, 10)]);
+
+        // bra 0
+        let mut interpreter =
+            Interpreter::new(make_mem(&[600]), &mut input, &mut output, &mut logger);
+        let mut calls = 0;
+        let res = interpreter.run_with_callback(5, |_| calls += 1);
+        assert!(matches!(res, Err(LNCError::StepLimit { max_steps: 5 })));
+        assert_eq!(calls, 5);
+    }
+
+    #[test]
+    fn halt_reasons() {
+        let run = |codes: &[usize]| {
+            let mut input = QueueInput::default();
+            let mut output = StackOutput::default();
+            let mut logger = NullLogger;
+
+            Interpreter::new(make_mem(codes), &mut input, &mut output, &mut logger)
+                .run_until_stopped(50)
+        };
+
+        // nop, hlt
+        assert_eq!(run(&[900, 0]), HaltReason::Halted { addr: 1 });
+        // bra 10, into empty memory
+        assert_eq!(run(&[610]), HaltReason::Halted { addr: 10 });
+        // bra 0
+        assert_eq!(run(&[600]), HaltReason::StepLimit { max_steps: 50 });
+        assert!(matches!(
+            run(&[901]),
+            HaltReason::Error(LNCError::Runtime { pc: 0, .. })
+        ));
+
+        // halted before running anything
+        let mut input = QueueInput::default();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+        let state = InterpreterState {
+            halted: true,
+            ..InterpreterState::new(make_mem(&[901]))
+        };
+        let mut interpreter = Interpreter::from_state(state, &mut input, &mut output, &mut logger);
+        assert_eq!(
+            interpreter.run_until_stopped(50),
+            HaltReason::Halted { addr: 0 }
+        );
+    }
+
+    #[test]
+    fn running_past_the_last_cell() {
+        let run = run_mem([900; 100], &[], |i| i);
+
+        assert_eq!(
+            run.result,
+            Err(LNCError::Runtime {
+                pc: 99,
+                msg: "error at address 99: ran past the end of memory".into()
+            })
+        );
+        assert_eq!(run.ins_count, 100);
+    }
+
+    #[test]
+    fn disabled_logger_gets_no_messages() {
+        #[derive(Default)]
+        struct DisabledLogger {
+            warnings: Vec<String>,
+        }
+
+        impl Log for DisabledLogger {
+            fn log(&mut self, msg: String) {
+                panic!("logged {msg:?} while disabled");
+            }
+
+            fn enabled(&self) -> bool {
+                false
+            }
+
+            fn warn(&mut self, msg: String) {
+                self.warnings.push(msg);
+            }
+        }
+
+        // every kind of instruction, including an overflow and underflow
+        let mem = make_mem(&[901, 312, 112, 212, 713, 813, 607, 900, 512, 902, 0]);
+
+        let mut input = QueueInput::new(&[999]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = DisabledLogger::default();
+
+        // warnings are still sent
+        let mut interpreter =
+            Interpreter::new(mem, &mut input, &mut output, &mut logger).with_branch_checks(7);
+
+        assert!(interpreter.run_to_halt(100).is_ok());
+        assert_eq!(output.stack, [999]);
+        assert_eq!(
+            logger.warnings,
+            ["warning @ address 6: branch to address 7, past the end of the program"]
+        );
+    }
+
+    #[test]
+    fn arithmetic_stats() {
+        // inp, add 9, add 9, sub 10, sub 9, hlt, then 600 and 700 as data
+        let mem = make_mem(&[901, 109, 109, 210, 209, 0, 0, 0, 0, 600, 700]);
+        let run = run_mem(mem, &[500], |i| i);
+        run.result.unwrap();
+
+        // 500 -> 100 -> 700 -> 0 -> 400
+        assert_eq!(run.state.acc, 400);
+        assert_eq!(run.overflow_count, 1);
+        assert_eq!(run.underflow_count, 1);
+        assert_eq!(run.max_acc, 700);
+    }
+
+    #[test]
+    fn run_to_halt_step_limit() {
+        // bra 00
+        let mem = make_mem(&[600]);
+
+        let mut input = QueueInput::default();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
+
+        assert_eq!(
+            interpreter.run_to_halt(50),
+            Err(LNCError::StepLimit { max_steps: 50 })
+        );
+        assert!(!interpreter.is_halted());
+        assert_eq!(interpreter.ins_count(), 50);
+    }
+
+    #[test]
+    fn run_to_halt_propagates_errors() {
+        // inp with an empty input queue
+        let run = run_mem(make_mem(&[901, 0]), &[], |i| i);
+
+        assert!(matches!(run.result, Err(LNCError::Runtime { pc: 0, .. })));
+    }
+
+    #[test]
+    fn sub_underflow_sets_neg_flag() {
+        let run = run_source("inp\nsub five\nout\nhlt\nfive: dat 5", &[3]);
+        assert_eq!(run.outputs, vec![998]);
+        assert!(run.state.neg_flag);
+
+        let run = run_source("inp\nsub five\nout\nhlt\nfive: dat 5", &[5]);
+        assert_eq!(run.outputs, vec![0]);
+        assert!(!run.state.neg_flag);
+    }
+
+    #[test]
+    fn add_overflow_wraps() {
+        let run = run_source("inp\nadd big\nout\nhlt\nbig: dat 999", &[7]);
+        assert_eq!(run.outputs, vec![6]);
+        assert_eq!(run.state.acc, 6);
+
+        // add clears neg_flag even after an underflow
+        let run = run_source("sub one\nadd one\nhlt\none: dat 1", &[]);
+        assert_eq!(run.state.acc, 0);
+        assert!(!run.state.neg_flag);
+    }
+
+    #[test]
+    fn brz_branches_on_zero() {
+        let src = "
+            inp
+            brz zero
+            out
+            hlt
+            zero: lda ten
+            out
+            hlt
+            ten: dat 10";
+
+        assert_eq!(run_source(src, &[0]).outputs, vec![10]);
+        assert_eq!(run_source(src, &[4]).outputs, vec![4]);
+    }
+
+    #[test]
+    fn brp_branches_unless_negative() {
+        // outputs 1 if input >= 3, otherwise 0
+        let src = "
+            inp
+            sub three
+            brp big
+            lda zero
+            out
+            hlt
+            big: lda one
+            out
+            hlt
+            zero: dat 0
+            one: dat 1
+            three: dat 3";
+
+        assert_eq!(run_source(src, &[2]).outputs, vec![0]);
+        assert_eq!(run_source(src, &[3]).outputs, vec![1]);
+        assert_eq!(run_source(src, &[9]).outputs, vec![1]);
+    }
+
+    #[test]
+    fn countdown_logs_each_output() {
+        let run = run_source(
+            "
+            inp
+            loop: out
+            brz done
+            sub one
+            bra loop
+            done: hlt
+            one: dat 1",
+            &[3],
+        );
+
+        assert_eq!(run.outputs, vec![3, 2, 1, 0]);
+        assert!(run.state.halted);
+        assert_eq!(
+            run.logs
+                .iter()
+                .filter(|m| m.ends_with("was output value"))
+                .count(),
+            4
+        );
+    }
+}
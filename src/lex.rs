@@ -1,9 +1,13 @@
 use std::iter::Peekable;
 use std::str::Chars;
 
+use crate::LNCError;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TokenKind {
     Number(usize),
+    // `#n`, a number that is explicitly a value rather than an address
+    Immediate(usize),
     Label(String),
     LabelDef(String),
     Load,
@@ -13,10 +17,17 @@ pub enum TokenKind {
     Input,
     Output,
     Halt,
+    Nop,
     BranchZero,
     BranchPositive,
     BranchAlways,
     Data,
+    Call,
+    Return,
+    // `lda`, `add` and `sub` of a value rather than an address
+    LoadImmediate,
+    AddImmediate,
+    SubtractImmediate,
     NewLine,
     Eof,
 
@@ -25,6 +36,36 @@ pub enum TokenKind {
     OpenSquareBracket,
     CloseSquareBracket,
     Comma,
+    Bang,
+    Plus,
+    Minus,
+    Equals,
+    // a `.` on its own, the address of the current instruction
+    Here,
+}
+
+impl TokenKind {
+    // How the token reads in an error message, e.g. "keyword 'add'".
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Number(n) => format!("number {n}"),
+            TokenKind::Immediate(n) => format!("value #{n}"),
+            TokenKind::Label(s) => format!("label '{s}'"),
+            TokenKind::LabelDef(s) => format!("label definition '{s}:'"),
+            TokenKind::NewLine => "end of line".into(),
+            TokenKind::Eof => "end of file".into(),
+            TokenKind::TestName(s) => format!("test '.{s}'"),
+            TokenKind::OpenSquareBracket => "'['".into(),
+            TokenKind::CloseSquareBracket => "']'".into(),
+            TokenKind::Comma => "','".into(),
+            TokenKind::Bang => "'!'".into(),
+            TokenKind::Plus => "'+'".into(),
+            TokenKind::Minus => "'-'".into(),
+            TokenKind::Equals => "'='".into(),
+            TokenKind::Here => "'.'".into(),
+            kw => format!("keyword '{}'", kw_name(kw)),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -54,22 +95,44 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn make_err_msg(&self, msg: String) -> String {
-        format!("error @ line {}: {}", self.line, msg)
+    fn make_err(&self, msg: String) -> LNCError {
+        // `start` counts bytes, but a column is a character
+        let col = self.source[..self.start].chars().count() + 1;
+
+        LNCError::Lex {
+            line: self.line,
+            col,
+            msg: format_error_with_caret(self.source, col, &msg),
+        }
     }
 
-    fn make_tokens(mut self) -> Result<Vec<Token>, String> {
+    fn make_tokens(mut self) -> Result<Vec<Token>, LNCError> {
         while let Some(ch) = self.consume() {
             match ch {
                 ';' => break,
+                '/' if self.peek() == Some(&'/') => break,
+                '.' if !self
+                    .peek()
+                    .is_some_and(|ch| ch.is_ascii_alphanumeric() || *ch == '_') =>
+                {
+                    self.add_token(TokenKind::Here)
+                }
                 '.' => self.test_name()?,
                 '[' => self.add_token(TokenKind::OpenSquareBracket),
                 ']' => self.add_token(TokenKind::CloseSquareBracket),
                 ',' => self.add_token(TokenKind::Comma),
+                '!' => self.add_token(TokenKind::Bang),
+                '+' => self.add_token(TokenKind::Plus),
+                '-' => self.add_token(TokenKind::Minus),
+                '=' => self.add_token(TokenKind::Equals),
+                // separates statements on the same line, just like a new line does
+                '|' => self.add_token(TokenKind::NewLine),
+                '#' => self.immediate()?,
+                '\'' => self.char_literal()?,
                 ch if ch.is_whitespace() => (),
                 ch if ch.is_ascii_digit() => self.number()?,
                 ch if ch.is_ascii_alphabetic() => self.kw_or_label()?,
-                _ => return Err(self.make_err_msg(format!("unexpected character '{}'", ch))),
+                _ => return Err(self.make_err(format!("unexpected character '{}'", ch))),
             }
 
             self.start = self.pos;
@@ -81,8 +144,9 @@ impl<'a> Lexer<'a> {
     }
 
     fn consume(&mut self) -> Option<char> {
-        self.pos += 1;
-        self.it.next()
+        let ch = self.it.next()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
     }
 
     fn peek(&mut self) -> Option<&char> {
@@ -114,32 +178,89 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn number(&mut self) -> Result<(), String> {
-        self.consume_while(|ch| ch.is_ascii_digit());
+    fn number(&mut self) -> Result<(), LNCError> {
+        match self.digits() {
+            Some(n) => self.add_token(TokenKind::Number(n)),
+            None => {
+                return Err(self.make_err(format!("invalid number literal \"{}\"", self.lexeme())))
+            }
+        }
+
+        Ok(())
+    }
+
+    // Digits, optionally grouped by single underscores between them (`1_00`).
+    fn digits(&mut self) -> Option<usize> {
+        self.consume_while(|ch| ch.is_ascii_digit() || *ch == '_');
+
+        let lexeme = self.lexeme();
+        if lexeme.ends_with('_') || lexeme.contains("__") {
+            return None;
+        }
+
+        lexeme.replace('_', "").parse().ok()
+    }
+
+    fn immediate(&mut self) -> Result<(), LNCError> {
+        if !self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            return Err(self.make_err("expected a number after '#'".into()));
+        }
+
+        self.start += 1;
 
-        match self.lexeme().parse::<usize>() {
-            Ok(n) => self.add_token(TokenKind::Number(n)),
-            Err(_) => {
-                return Err(
-                    self.make_err_msg(format!("invalid number literal \"{}\"", self.lexeme()))
-                )
+        match self.digits() {
+            Some(n) => self.add_token(TokenKind::Immediate(n)),
+            None => {
+                return Err(self.make_err(format!("invalid number literal \"#{}\"", self.lexeme())))
             }
         }
 
         Ok(())
     }
 
-    fn kw_or_label(&mut self) -> Result<(), String> {
+    fn char_literal(&mut self) -> Result<(), LNCError> {
+        let ch = match self.consume() {
+            Some('\\') => match self.consume() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some('\'') => '\'',
+                Some('\\') => '\\',
+                Some(ch) => return Err(self.make_err(format!("unknown escape sequence '\\{ch}'"))),
+                None => return Err(self.make_err("unterminated character literal".into())),
+            },
+            Some('\'') => return Err(self.make_err("empty character literal".into())),
+            Some(ch) => ch,
+            None => return Err(self.make_err("unterminated character literal".into())),
+        };
+
+        if !ch.is_ascii() {
+            return Err(self.make_err(format!(
+                "character literal '{ch}' is not an ASCII character"
+            )));
+        }
+
+        if self.consume() != Some('\'') {
+            return Err(
+                self.make_err("character literals must contain exactly one character".into())
+            );
+        }
+
+        self.add_token(TokenKind::Number(ch as usize));
+
+        Ok(())
+    }
+
+    fn kw_or_label(&mut self) -> Result<(), LNCError> {
         self.consume_while(|ch| ch.is_ascii_alphanumeric() || *ch == '_');
 
         let lexeme = self.lexeme();
         let is_label_def = matches!(self.peek(), Some(':'));
 
-        if let Some(kind) = map_kw(&lexeme) {
+        // keywords are case-insensitive, labels are not
+        if let Some(kind) = map_kw(&lexeme.to_lowercase()) {
             if is_label_def {
-                return Err(
-                    self.make_err_msg(format!("cannot use keyword \"{lexeme}\" as label name"))
-                );
+                return Err(self.make_err(format!("cannot use keyword \"{lexeme}\" as label name")));
             }
             self.add_token(kind);
         } else if is_label_def {
@@ -152,15 +273,15 @@ impl<'a> Lexer<'a> {
         Ok(())
     }
 
-    fn test_name(&mut self) -> Result<(), String> {
+    fn test_name(&mut self) -> Result<(), LNCError> {
         if let Some(ch) = self.consume() {
             if !(ch.is_ascii_alphabetic() || ch == '_') {
-                return Err(format!(
+                return Err(self.make_err(format!(
                     "unexpected character '{ch}': test names must start with a letter or '_'"
-                ));
+                )));
             }
         } else {
-            return Err("tests must have a name".into());
+            return Err(self.make_err("tests must have a name".into()));
         }
         self.consume_while(|ch| ch.is_ascii_alphanumeric() || *ch == '_');
 
@@ -173,6 +294,19 @@ impl<'a> Lexer<'a> {
     }
 }
 
+// Puts the source line under the message, with a `^` below column `col`
+// (counted in characters, from 1). Tabs before the column are kept so the
+// caret still lines up.
+fn format_error_with_caret(line_str: &str, col: usize, msg: &str) -> String {
+    let pad: String = line_str
+        .chars()
+        .take(col - 1)
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    format!("{msg}\n    {line_str}\n    {pad}^")
+}
+
 fn map_kw(word: &str) -> Option<TokenKind> {
     match word {
         "lda" => Some(TokenKind::Load),
@@ -182,35 +316,68 @@ fn map_kw(word: &str) -> Option<TokenKind> {
         "inp" => Some(TokenKind::Input),
         "out" => Some(TokenKind::Output),
         "hlt" => Some(TokenKind::Halt),
+        "nop" => Some(TokenKind::Nop),
         "brz" => Some(TokenKind::BranchZero),
         "brp" => Some(TokenKind::BranchPositive),
         "bra" => Some(TokenKind::BranchAlways),
         "dat" => Some(TokenKind::Data),
+        "call" => Some(TokenKind::Call),
+        "ret" => Some(TokenKind::Return),
+        "ldi" => Some(TokenKind::LoadImmediate),
+        "addi" => Some(TokenKind::AddImmediate),
+        "subi" => Some(TokenKind::SubtractImmediate),
         _ => None,
     }
 }
 
-pub fn tokenize(source: &str) -> Result<Vec<Token>, (Vec<Token>, String)> {
+// The inverse of `map_kw`.
+fn kw_name(kind: &TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Load => "lda",
+        TokenKind::Store => "sto",
+        TokenKind::Add => "add",
+        TokenKind::Subtract => "sub",
+        TokenKind::Input => "inp",
+        TokenKind::Output => "out",
+        TokenKind::Halt => "hlt",
+        TokenKind::Nop => "nop",
+        TokenKind::BranchZero => "brz",
+        TokenKind::BranchPositive => "brp",
+        TokenKind::BranchAlways => "bra",
+        TokenKind::Data => "dat",
+        TokenKind::Call => "call",
+        TokenKind::Return => "ret",
+        TokenKind::LoadImmediate => "ldi",
+        TokenKind::AddImmediate => "addi",
+        TokenKind::SubtractImmediate => "subi",
+        _ => unreachable!("not a keyword: {kind:?}"),
+    }
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, (Vec<Token>, LNCError)> {
     let mut tokens = vec![];
     let mut errors = vec![];
 
     for (i, line) in source.lines().enumerate() {
+        // `lines` leaves the `\r` on a last line that has no `\n` after it
+        let line = line.strip_suffix('\r').unwrap_or(line);
         let lexer = Lexer::new(i + 1, line);
 
         match lexer.make_tokens() {
-            Ok(t) => tokens.extend(t.into_iter()),
+            Ok(t) => tokens.extend(t),
             Err(e) => errors.push(e),
         }
     }
 
     if errors.is_empty() {
+        // an empty source still has a first line to report errors on
         tokens.push(Token {
             kind: TokenKind::Eof,
-            line: source.lines().count(),
+            line: source.lines().count().max(1),
         });
         Ok(tokens)
     } else {
-        Err((tokens, errors.join("\n")))
+        Err((tokens, LNCError::from_vec(errors)))
     }
 }
 
@@ -231,12 +398,83 @@ mod tests {
         assert_eq!(single("inp"), TokenKind::Input);
         assert_eq!(single("out"), TokenKind::Output);
         assert_eq!(single("hlt"), TokenKind::Halt);
+        assert_eq!(single("nop"), TokenKind::Nop);
         assert_eq!(single("brz"), TokenKind::BranchZero);
         assert_eq!(single("brp"), TokenKind::BranchPositive);
         assert_eq!(single("bra"), TokenKind::BranchAlways);
         assert_eq!(single("dat"), TokenKind::Data);
     }
 
+    #[test]
+    fn tokenize_kw_any_case() {
+        assert_eq!(single("LDA"), TokenKind::Load);
+        assert_eq!(single("Sto"), TokenKind::Store);
+        assert_eq!(single("aDD"), TokenKind::Add);
+        assert_eq!(single("SuB"), TokenKind::Subtract);
+        assert_eq!(single("INP"), TokenKind::Input);
+        assert_eq!(single("Out"), TokenKind::Output);
+        assert_eq!(single("Hlt"), TokenKind::Halt);
+        assert_eq!(single("NOP"), TokenKind::Nop);
+        assert_eq!(single("BRZ"), TokenKind::BranchZero);
+        assert_eq!(single("bRp"), TokenKind::BranchPositive);
+        assert_eq!(single("BRA"), TokenKind::BranchAlways);
+        assert_eq!(single("DAT"), TokenKind::Data);
+
+        assert!(tokenize("Add:").is_err());
+        assert!(tokenize("HLT:").is_err());
+    }
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    #[test]
+    fn eof_line() {
+        let eof_line = |source| tokenize(source).unwrap().last().unwrap().line;
+
+        assert_eq!(eof_line(""), 1);
+        assert_eq!(eof_line("hlt"), 1);
+        assert_eq!(eof_line("hlt\n"), 1);
+        assert_eq!(eof_line("inp\nadd 5"), 2);
+        assert_eq!(eof_line("inp\r\nadd 5\r\n"), 2);
+        assert_eq!(eof_line("inp\n\n"), 2);
+    }
+
+    #[test]
+    fn caret_under_the_error() {
+        let err = |source| tokenize(source).unwrap_err().1.to_string();
+
+        assert_eq!(
+            err("  lda ?"),
+            "error @ line 1:7: unexpected character '?'\n      lda ?\n          ^"
+        );
+        assert_eq!(
+            err("\tlda\t?"),
+            "error @ line 1:6: unexpected character '?'\n    \tlda\t?\n    \t   \t^"
+        );
+
+        assert_eq!(
+            err("dat 'é'"),
+            "error @ line 1:5: character literal 'é' is not an ASCII character\n    dat 'é'\n        ^"
+        );
+    }
+
+    #[test]
+    fn slash_comments() {
+        assert_eq!(kinds("add 5 // note"), kinds("add 5 ; note"));
+        assert_eq!(kinds("hlt//\nout"), kinds("hlt\nout"));
+
+        assert_eq!(
+            tokenize("add 5 / note").unwrap_err().1.to_string(),
+            "error @ line 1:7: unexpected character '/'\n    add 5 / note\n          ^"
+        );
+        assert!(tokenize("/").is_err());
+    }
+
     #[test]
     fn tokenize_label() {
         assert_eq!(single("test_label"), TokenKind::Label("test_label".into()));
@@ -246,6 +484,8 @@ mod tests {
         );
         assert_eq!(single("HasCaps"), TokenKind::Label("HasCaps".into()));
         assert_eq!(single("HasNums123"), TokenKind::Label("HasNums123".into()));
+        assert_eq!(single("Loop"), TokenKind::Label("Loop".into()));
+        assert_ne!(single("Loop"), TokenKind::Label("loop".into()));
     }
 
     #[test]
@@ -257,10 +497,13 @@ mod tests {
         assert!(tokenize("inp:").is_err());
         assert!(tokenize("out:").is_err());
         assert!(tokenize("hlt:").is_err());
+        assert!(tokenize("nop:").is_err());
         assert!(tokenize("brz:").is_err());
         assert!(tokenize("brp:").is_err());
         assert!(tokenize("bra:").is_err());
         assert!(tokenize("dat:").is_err());
+        // `var` is only a keyword after a variable name
+        assert!(tokenize("var:").is_ok());
     }
 
     #[test]
@@ -270,15 +513,149 @@ mod tests {
         assert!(tokenize("12.3").is_err());
     }
 
+    #[test]
+    fn tokenize_num_with_underscores() {
+        assert_eq!(single("1_0"), TokenKind::Number(10));
+        assert_eq!(single("1_00"), TokenKind::Number(100));
+        assert_eq!(single("9_9_9"), TokenKind::Number(999));
+        assert_eq!(single("#1_0"), TokenKind::Immediate(10));
+
+        assert!(tokenize("_1").is_err());
+        assert!(tokenize("#_1").is_err());
+        assert_eq!(
+            tokenize("1_").unwrap_err().1.to_string(),
+            "error @ line 1:1: invalid number literal \"1_\"\n    1_\n    ^"
+        );
+        assert!(tokenize("1__0").is_err());
+        assert!(tokenize("#1_").is_err());
+    }
+
+    #[test]
+    fn tokenize_char_literal() {
+        assert_eq!(single("'A'"), TokenKind::Number(65));
+        assert_eq!(single("'a'"), TokenKind::Number(97));
+        assert_eq!(single("' '"), TokenKind::Number(32));
+        assert_eq!(single("';'"), TokenKind::Number(59));
+        assert_eq!(single("'\\n'"), TokenKind::Number(10));
+        assert_eq!(single("'\\t'"), TokenKind::Number(9));
+        assert_eq!(single("'\\0'"), TokenKind::Number(0));
+        assert_eq!(single("'\\''"), TokenKind::Number(39));
+        assert_eq!(single("'\\\\'"), TokenKind::Number(92));
+
+        assert!(tokenize("''").is_err());
+        assert!(tokenize("'AB'").is_err());
+        assert!(tokenize("'A").is_err());
+        assert!(tokenize("'").is_err());
+        assert!(tokenize("'\\q'").is_err());
+        assert!(tokenize("'é'").is_err());
+    }
+
     #[test]
     fn unrecognised_char() {
         assert!(tokenize(":").is_err());
         assert!(tokenize("*").is_err());
-        assert!(tokenize("+").is_err());
-        assert!(tokenize("-").is_err());
+        assert!(tokenize("$").is_err());
         assert!(tokenize("add 23 ; !@#$%^&*()").is_ok());
     }
 
+    #[test]
+    fn tokenize_immediate() {
+        assert_eq!(single("#65"), TokenKind::Immediate(65));
+        assert_eq!(single("#0"), TokenKind::Immediate(0));
+
+        assert!(tokenize("#").is_err());
+        assert!(tokenize("# 65").is_err());
+        assert!(tokenize("#abc").is_err());
+    }
+
+    #[test]
+    fn tokenize_offset() {
+        assert_eq!(
+            kinds("lda table+2"),
+            [
+                TokenKind::Load,
+                TokenKind::Label("table".into()),
+                TokenKind::Plus,
+                TokenKind::Number(2),
+                TokenKind::NewLine,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_relative() {
+        assert_eq!(
+            kinds("bra .-1"),
+            [
+                TokenKind::BranchAlways,
+                TokenKind::Here,
+                TokenKind::Minus,
+                TokenKind::Number(1),
+                TokenKind::NewLine,
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(kinds("brz .")[1], TokenKind::Here);
+        assert_eq!(kinds(". +2")[0], TokenKind::Here);
+        assert_eq!(kinds(".test [] []")[0], TokenKind::TestName("test".into()));
+        assert!(tokenize(".5 [] []").is_err());
+    }
+
+    #[test]
+    fn describe_tokens() {
+        assert_eq!(TokenKind::Add.describe(), "keyword 'add'");
+        assert_eq!(TokenKind::BranchPositive.describe(), "keyword 'brp'");
+        assert_eq!(TokenKind::Label("x".into()).describe(), "label 'x'");
+        assert_eq!(TokenKind::Number(5).describe(), "number 5");
+        assert_eq!(TokenKind::NewLine.describe(), "end of line");
+
+        // every keyword names itself
+        for word in [
+            "lda", "sto", "add", "sub", "inp", "out", "hlt", "nop", "brz", "brp", "bra", "dat",
+            "call", "ret",
+        ] {
+            assert_eq!(
+                map_kw(word).unwrap().describe(),
+                format!("keyword '{word}'")
+            );
+        }
+    }
+
+    #[test]
+    fn tokenize_separator() {
+        assert_eq!(
+            kinds("inp | out|hlt"),
+            [
+                TokenKind::Input,
+                TokenKind::NewLine,
+                TokenKind::Output,
+                TokenKind::NewLine,
+                TokenKind::Halt,
+                TokenKind::NewLine,
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(kinds("'|'")[0], TokenKind::Number('|' as usize));
+        assert_eq!(
+            kinds("hlt ; a | b"),
+            [TokenKind::Halt, TokenKind::NewLine, TokenKind::Eof]
+        );
+    }
+
+    #[test]
+    fn error_position() {
+        let (_, e) = tokenize("hlt\nadd 1 * 2").unwrap_err();
+        assert_eq!(
+            e,
+            LNCError::Lex {
+                line: 2,
+                col: 7,
+                msg: "unexpected character '*'\n    add 1 * 2\n          ^".into()
+            }
+        );
+    }
+
     #[test]
     fn tokenize_lnc_test() {
         assert_eq!(
@@ -288,8 +665,36 @@ mod tests {
         assert_eq!(single("["), TokenKind::OpenSquareBracket);
         assert_eq!(single("]"), TokenKind::CloseSquareBracket);
         assert_eq!(single(","), TokenKind::Comma);
+        assert_eq!(single("!"), TokenKind::Bang);
+        assert_eq!(single("="), TokenKind::Equals);
+        assert_eq!(single("+"), TokenKind::Plus);
 
         assert!(tokenize(".1").is_err());
         assert!(tokenize(".1test").is_err());
     }
+
+    #[test]
+    fn crlf_line_endings() {
+        let lf = ".adds [1, 2] [3]\nloop: inp ; comment\nadd 'A'\nbra loop\n";
+        let crlf = lf.replace('\n', "\r\n");
+        let lines = |source: &str| -> Vec<usize> {
+            tokenize(source).unwrap().iter().map(|t| t.line).collect()
+        };
+
+        assert_eq!(kinds(&crlf), kinds(lf));
+        assert_eq!(lines(&crlf), lines(lf));
+        assert_eq!(kinds("hlt\r"), kinds("hlt"));
+        // a stray `\r` within a line is whitespace
+        assert_eq!(kinds("add\r5"), kinds("add 5"));
+    }
+
+    #[test]
+    fn crlf_error_position() {
+        let err = |source: &str| tokenize(source).unwrap_err().1;
+
+        assert_eq!(err("hlt\r\nadd 1 * 2\r\n"), err("hlt\nadd 1 * 2\n"));
+        // the last line need not end in `\n`
+        assert_eq!(err("hlt\r\nadd 1 * 2\r"), err("hlt\nadd 1 * 2"));
+        assert_eq!(err(".a\r\n.1\r\n"), err(".a\n.1\n"));
+    }
 }
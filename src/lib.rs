@@ -1,19 +1,183 @@
+mod ansi;
 mod assembler;
-mod interpreter;
+mod cfg;
+mod disasm;
+mod error;
 mod lex;
+mod lint;
 mod parse;
-mod vec_io;
+mod sections;
+mod trace;
+
+#[cfg(test)]
+mod testing;
 
 pub mod cli;
+pub mod interpreter;
+pub mod vec_io;
+
+pub use assembler::encode;
+pub use error::LNCError;
+pub use parse::{Address, Instruction};
+
+use std::io::Read;
 
+use interpreter::{BrpMode, HaltReason, Interpreter};
 use parse::{LNCTest, ParseInfo};
+use vec_io::{NullLogger, QueueInput, StackOutput};
 
 pub struct LNCProgram {
     pub mem: [usize; 100],
     pub parse_info: ParseInfo,
+    pub warnings: Vec<String>,
+}
+
+// What a run of a program did, see `LNCProgram::run_headless`.
+#[derive(Debug)]
+pub struct ProgramStats {
+    pub instruction_count: usize,
+    pub outputs: Vec<usize>,
+    // indexed by opcode, the first digit of each executed instruction
+    pub opcode_counts: [usize; 10],
+    pub overflow_count: usize,
+    pub halt_reason: HaltReason,
+}
+
+impl LNCProgram {
+    // The binary image is each of the 100 cells, in address order, as a
+    // big-endian u16 (200 bytes in total).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.mem
+            .iter()
+            .flat_map(|&cell| (cell as u16).to_be_bytes())
+            .collect()
+    }
+
+    // Every cell as three zero-padded digits, ten cells per line. This is the
+    // format read back by `--from-mem`.
+    pub fn to_machine_text(&self) -> String {
+        self.mem
+            .chunks(10)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| format!("{cell:03}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+            + "\n"
+    }
+
+    /// Decodes every memory cell back into an instruction, so a program can be
+    /// inspected without its source. Cells that aren't a valid instruction
+    /// become `Instruction::Data`.
+    pub fn decode(&self) -> Vec<(usize, Instruction)> {
+        self.mem
+            .iter()
+            .enumerate()
+            .map(|(addr, &code)| (addr, disasm::decode(code)))
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<LNCProgram, String> {
+        if bytes.len() != 200 {
+            return Err(format!(
+                "invalid program image: expected 200 bytes, found {}",
+                bytes.len()
+            ));
+        }
+
+        let mut mem = [0; 100];
+
+        for (addr, pair) in bytes.chunks_exact(2).enumerate() {
+            let cell = u16::from_be_bytes([pair[0], pair[1]]) as usize;
+
+            if cell >= 1000 {
+                return Err(format!(
+                    "invalid program image: value {cell} at address {addr} is too large"
+                ));
+            }
+
+            mem[addr] = cell;
+        }
+
+        Ok(LNCProgram {
+            mem,
+            parse_info: ParseInfo::new(),
+            warnings: vec![],
+        })
+    }
+
+    // Runs the program on `inputs` without touching stdin or stdout. Only
+    // inputs that are too large are an error: a program that fails at runtime
+    // says so in `halt_reason`.
+    pub fn run_headless(
+        &self,
+        inputs: &[usize],
+        max_steps: usize,
+        brp_mode: BrpMode,
+    ) -> Result<ProgramStats, String> {
+        let mut input = QueueInput::new(inputs)?;
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+
+        let mut interpreter = Interpreter::new(self.mem, &mut input, &mut output, &mut logger)
+            .with_brp_mode(brp_mode);
+        let halt_reason = interpreter.run_until_stopped(max_steps);
+        let (instruction_count, opcode_counts, overflow_count) = (
+            interpreter.ins_count(),
+            interpreter.opcode_counts(),
+            interpreter.overflow_count(),
+        );
+
+        Ok(ProgramStats {
+            instruction_count,
+            outputs: output.stack,
+            opcode_counts,
+            overflow_count,
+            halt_reason,
+        })
+    }
+}
+
+pub fn make_program(source: &str) -> Result<LNCProgram, LNCError> {
+    let (parse_info, mem) = assemble_source(source)?;
+    let warnings = lint::check(&parse_info, &mem);
+
+    Ok(LNCProgram {
+        mem,
+        parse_info,
+        warnings,
+    })
+}
+
+// Like `make_program`, but a `dat` that can be reached as code and isn't a
+// valid instruction is an error rather than a warning.
+pub fn make_strict_program(source: &str) -> Result<LNCProgram, LNCError> {
+    let program = make_program(source)?;
+
+    let errors: Vec<LNCError> = lint::reachable_undefined_data(&program.parse_info, &program.mem)
+        .into_iter()
+        .map(|(paddr, value)| LNCError::Assemble {
+            msg: format!(
+                "error @ address {paddr}: \"dat {value}\" can be reached as code, but {value:03} is an undefined instruction"
+            ),
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(LNCError::from_vec(errors))
+    }
 }
 
-pub fn make_program(source: &str) -> Result<LNCProgram, String> {
+// Assembles for a memory of `N` cells, see `MemSize`. The lints only know
+// about classic 100-cell programs, so this doesn't run them.
+pub(crate) fn assemble_source<const N: usize>(
+    source: &str,
+) -> Result<(ParseInfo, [usize; N]), LNCError> {
     let mut errors = vec![];
 
     let tokens = match lex::tokenize(source) {
@@ -23,24 +187,224 @@ pub fn make_program(source: &str) -> Result<LNCProgram, String> {
             toks
         }
     };
-    let parse_info = match parse::parse(&tokens) {
+    let mut parse_info = match parse::parse(&tokens, N) {
         Ok(pi) => pi,
         Err((pi, e)) => {
             errors.push(e);
             pi
         }
     };
+    if let Err(e) = assembler::allocate_vars(&mut parse_info, N) {
+        errors.push(e);
+        return Err(LNCError::from_vec(errors));
+    }
     let mem = match assembler::assemble(&parse_info) {
         Ok(m) => m,
         Err(e) => {
             errors.push(e);
-            return Err(errors.join("\n"));
+            return Err(LNCError::from_vec(errors));
         }
     };
 
     if !errors.is_empty() {
-        Err(errors.join("\n"))
+        Err(LNCError::from_vec(errors))
     } else {
-        Ok(LNCProgram { mem, parse_info })
+        Ok((parse_info, mem))
+    }
+}
+
+// The whole source is read before assembling it, since labels can be used
+// before they are defined.
+pub fn make_program_from_reader<R: Read>(mut reader: R) -> Result<LNCProgram, String> {
+    let mut source = String::new();
+    reader
+        .read_to_string(&mut source)
+        .map_err(|e| format!("error reading source: {e}"))?;
+
+    Ok(make_program(&source)?)
+}
+
+// Parses a tests file: test definitions to run against a separately
+// assembled program.
+pub fn make_tests(source: &str) -> Result<Vec<LNCTest>, LNCError> {
+    let (tokens, lex_error) = match lex::tokenize(source) {
+        Ok(toks) => (toks, None),
+        Err((toks, e)) => (toks, Some(e)),
+    };
+
+    match (parse::parse_tests(&tokens), lex_error) {
+        (Ok(tests), None) => Ok(tests),
+        (Ok(_), Some(e)) => Err(e),
+        (Err(e), None) => Err(e),
+        (Err(e), Some(lex_error)) => Err(LNCError::from_vec(vec![lex_error, e])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict_rejects_reachable_undefined_data() {
+        // `dat 5` typed where `add five` was meant, and it runs
+        let source = "inp\ndat 5\nout\nhlt\nfive: dat 5";
+
+        assert_eq!(make_program(source).unwrap().warnings.len(), 1);
+        assert_eq!(
+            make_strict_program(source).err().unwrap().to_string(),
+            "error @ address 1: \"dat 5\" can be reached as code, but 005 is an undefined instruction"
+        );
+
+        // data after a hlt is never run
+        assert!(make_strict_program("inp\nout\nhlt\nfive: dat 5").is_ok());
+    }
+
+    #[test]
+    fn headless_run_stats() {
+        // adds 500 to each input until it reads a 0
+        let program = make_program(
+            "
+            loop: inp
+            brz end
+            add big
+            out
+            bra loop
+            end: hlt
+            big: dat 500",
+        )
+        .unwrap();
+
+        let stats = program
+            .run_headless(&[100, 600, 0], 100, BrpMode::default())
+            .unwrap();
+        assert_eq!(stats.instruction_count, 13);
+        assert_eq!(stats.outputs, vec![600, 100]);
+        assert_eq!(stats.opcode_counts, [1, 2, 0, 0, 0, 0, 2, 3, 0, 5]);
+        assert_eq!(stats.overflow_count, 1);
+        assert_eq!(stats.halt_reason, HaltReason::Halted { addr: 5 });
+
+        let stats = program.run_headless(&[1], 100, BrpMode::default()).unwrap();
+        assert!(matches!(stats.halt_reason, HaltReason::Error(_)));
+        assert_eq!(stats.outputs, vec![501]);
+
+        assert!(program
+            .run_headless(&[1000], 100, BrpMode::default())
+            .is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut program = make_program("inp\nout\nhlt\ndat 999").unwrap();
+        program.mem[99] = 123;
+
+        let bytes = program.to_bytes();
+        assert_eq!(bytes.len(), 200);
+        assert_eq!(bytes[..8], [3, 133, 3, 134, 0, 0, 3, 231]);
+
+        let loaded = LNCProgram::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.mem, program.mem);
+        assert!(loaded.parse_info.instructions.is_empty());
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        use Address::Numeric;
+
+        let program = make_program(
+            "
+            loop: inp
+            brz done
+            out 3
+            sub one
+            bra loop
+            done: hlt
+            one: dat 1
+            dat 999",
+        )
+        .unwrap();
+
+        let decoded: Vec<_> = program.decode().into_iter().take(9).collect();
+        assert_eq!(
+            decoded,
+            [
+                (0, Instruction::Input),
+                (1, Instruction::BranchZero(Numeric(5))),
+                (2, Instruction::OutputDevice(3)),
+                (3, Instruction::Subtract(Numeric(6))),
+                (4, Instruction::BranchAlways(Numeric(0))),
+                (5, Instruction::Halt),
+                (6, Instruction::Data(1)),
+                (7, Instruction::Data(999)),
+                (8, Instruction::Halt),
+            ]
+        );
+    }
+
+    #[test]
+    fn machine_text_is_padded() {
+        let program = make_program("inp\nadd seven\nout\nhlt\nseven: dat 7").unwrap();
+        let text = program.to_machine_text();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "901 104 902 000 007 000 000 000 000 000");
+        assert!(lines[1..].iter().all(|&line| line == lines[9]));
+        assert!(text.ends_with("000\n"));
+    }
+
+    #[test]
+    fn program_from_reader() {
+        use std::io::Cursor;
+
+        let source = "lda end\nout\nend: hlt";
+        let program = make_program_from_reader(Cursor::new(source)).unwrap();
+        assert_eq!(program.mem, make_program(source).unwrap().mem);
+
+        assert_eq!(
+            make_program_from_reader(Cursor::new("lda nowhere")).err(),
+            Some("Label 'nowhere' is not defined (used by instruction at address 0)".into())
+        );
+        let err = make_program_from_reader(Cursor::new([0xff, 0xfe])).err();
+        assert!(err.unwrap().starts_with("error reading source"));
+    }
+
+    #[test]
+    fn bytes_invalid_image() {
+        assert!(LNCProgram::from_bytes(&[]).is_err());
+        assert!(LNCProgram::from_bytes(&[0; 199]).is_err());
+        assert!(LNCProgram::from_bytes(&[0; 201]).is_err());
+
+        let mut bytes = [0; 200];
+        bytes[198..].copy_from_slice(&1000u16.to_be_bytes());
+        assert!(LNCProgram::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn tests_from_separate_source() {
+        let tests = make_tests(".double [2] [4]\n.twice [1, 2] [2, 4] ; comment").unwrap();
+        assert_eq!(tests.len(), 2);
+        assert_eq!(tests[1].inputs, [1, 2]);
+
+        assert!(make_tests("").unwrap().is_empty());
+        assert!(make_tests("hlt").is_err());
+
+        let err = make_tests(".a [1] [2]\n.b [1] @").unwrap_err();
+        assert!(matches!(err.errors()[0], LNCError::Lex { line: 2, .. }));
+    }
+
+    #[test]
+    fn crlf_source() {
+        let lf = "; doubles its input\n.double [2] [4]\ninp\nsto x\nadd x\nout\nhlt\nx: dat\n";
+        let crlf = lf.replace('\n', "\r\n");
+
+        let from_lf = make_program(lf).unwrap();
+        let from_crlf = make_program(&crlf).unwrap();
+        assert_eq!(from_crlf.mem, from_lf.mem);
+        assert_eq!(from_crlf.parse_info.lines, from_lf.parse_info.lines);
+        assert_eq!(from_crlf.parse_info.tests, from_lf.parse_info.tests);
+        assert_eq!(from_crlf.parse_info.label_map, from_lf.parse_info.label_map);
+
+        let err = |source: &str| make_program(source).err().unwrap();
+        assert_eq!(err("inp\r\nsto y\r\n"), err("inp\nsto y\n"));
     }
 }
@@ -0,0 +1,231 @@
+use crate::disasm;
+use crate::parse::{Instruction, ParseInfo};
+
+pub fn check(parse_info: &ParseInfo, mem: &[usize; 100]) -> Vec<String> {
+    let mut warnings = vec![];
+
+    warnings.extend(arith_on_code(parse_info, mem));
+    warnings.extend(executable_data(parse_info));
+    warnings.extend(undefined_data_in_code_path(parse_info, mem));
+    warnings.extend(branch_to_self(parse_info, mem));
+
+    warnings
+}
+
+fn arith_on_code(parse_info: &ParseInfo, mem: &[usize; 100]) -> Vec<String> {
+    let mut warnings = vec![];
+
+    for (paddr, ins) in parse_info.instructions.iter().enumerate() {
+        let mnemonic = match ins {
+            Instruction::Add(_) => "add",
+            Instruction::Subtract(_) => "sub",
+            _ => continue,
+        };
+
+        let target = mem[paddr] % 100;
+
+        if target < parse_info.instructions.len()
+            && !matches!(parse_info.instructions[target], Instruction::Data(_))
+        {
+            warnings.push(format!(
+                "warning @ address {paddr}: \"{mnemonic} {target:02}\" uses the instruction at address {target} as an operand (did you mean to use a dat value?)"
+            ));
+        }
+    }
+
+    warnings
+}
+
+// Data in the 900s shares its encoding with nop and the I/O instructions, so a
+// stray branch into it runs those silently instead of failing.
+fn executable_data(parse_info: &ParseInfo) -> Vec<String> {
+    let mut warnings = vec![];
+
+    for (paddr, ins) in parse_info.instructions.iter().enumerate() {
+        let Instruction::Data(value) = *ins else {
+            continue;
+        };
+
+        if value >= 900 && !matches!(disasm::decode(value), Instruction::Data(_)) {
+            warnings.push(format!(
+                "warning @ address {paddr}: \"dat {value}\" would run as \"{}\" if executed",
+                disasm::mnemonic(value)
+            ));
+        }
+    }
+
+    warnings
+}
+
+// `hlt` only ever assembles to 000, so 001 to 099 can only come from a `dat`,
+// and they are undefined instructions if run. Such a `dat` is probably meant
+// to be code when the instruction before it falls through into it, or a branch
+// jumps to it.
+fn undefined_data_in_code_path(parse_info: &ParseInfo, mem: &[usize; 100]) -> Vec<String> {
+    reachable_undefined_data(parse_info, mem)
+        .into_iter()
+        .map(|(paddr, value)| {
+            format!(
+                "warning @ address {paddr}: \"dat {value}\" can be reached as code, but {value:03} is an undefined instruction"
+            )
+        })
+        .collect()
+}
+
+// The address and value of each such `dat`.
+pub fn reachable_undefined_data(parse_info: &ParseInfo, mem: &[usize; 100]) -> Vec<(usize, usize)> {
+    let instructions = &parse_info.instructions;
+    let mut found = vec![];
+
+    for (paddr, ins) in instructions.iter().enumerate() {
+        let Instruction::Data(value @ 1..=99) = *ins else {
+            continue;
+        };
+
+        let falls_into = paddr
+            .checked_sub(1)
+            .map(|prev| &instructions[prev])
+            .is_some_and(|prev| {
+                !matches!(
+                    prev,
+                    Instruction::Data(_) | Instruction::Halt | Instruction::BranchAlways(_)
+                )
+            });
+        let branched_to = instructions.iter().enumerate().any(|(addr, ins)| {
+            matches!(
+                ins,
+                Instruction::BranchZero(_)
+                    | Instruction::BranchPositive(_)
+                    | Instruction::BranchAlways(_)
+            ) && mem[addr] % 100 == paddr
+        });
+
+        if falls_into || branched_to {
+            found.push((paddr, value));
+        }
+    }
+
+    found
+}
+
+// A `bra` to its own address can never get out. Whether `brz` and `brp` loop
+// forever depends on the accumulator, which isn't known here.
+fn branch_to_self(parse_info: &ParseInfo, mem: &[usize; 100]) -> Vec<String> {
+    let mut warnings = vec![];
+
+    for (paddr, ins) in parse_info.instructions.iter().enumerate() {
+        if matches!(ins, Instruction::BranchAlways(_)) && mem[paddr] % 100 == paddr {
+            warnings.push(format!(
+                "warning @ address {paddr}: \"bra {paddr:02}\" branches to itself, an infinite loop"
+            ));
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assembler, lex, parse};
+
+    fn lint_src(source: &str) -> Vec<String> {
+        let tokens = lex::tokenize(source).unwrap();
+        let parse_info = parse::parse(&tokens, 100).unwrap();
+        let mem: [usize; 100] = assembler::assemble(&parse_info).unwrap();
+        check(&parse_info, &mem)
+    }
+
+    #[test]
+    fn warns_on_branch_to_self() {
+        assert_eq!(
+            lint_src("inp\nloop: bra loop"),
+            ["warning @ address 1: \"bra 01\" branches to itself, an infinite loop"]
+        );
+        assert_eq!(lint_src("bra .").len(), 1);
+
+        // these can fall through
+        assert!(lint_src("loop: brz loop\nloop2: brp loop2\nhlt").is_empty());
+        assert!(lint_src("loop: out\nbra loop").is_empty());
+    }
+
+    #[test]
+    fn warns_on_arith_with_code_operand() {
+        let warnings = lint_src(
+            "
+            inp
+            add 1
+            sub 0
+            out
+            hlt",
+        );
+
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("address 1"));
+        assert!(warnings[1].contains("address 2"));
+    }
+
+    #[test]
+    fn no_warning_on_arith_with_data_operand() {
+        let warnings = lint_src(
+            "
+            inp
+            add one
+            sub one
+            out
+            hlt
+            one: dat 1",
+        );
+
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn no_warning_on_arith_past_program() {
+        assert!(lint_src("add 50\nhlt").is_empty());
+    }
+
+    #[test]
+    fn warns_on_undefined_data_in_code_path() {
+        let warnings = lint_src(
+            "
+            inp
+            dat 5
+            brz skip
+            hlt
+            dat 6
+            skip: dat 7
+            bra end
+            dat 8
+            end: hlt
+            dat 0, 9",
+        );
+
+        assert_eq!(
+            warnings,
+            [
+                "warning @ address 1: \"dat 5\" can be reached as code, but 005 is an undefined instruction",
+                "warning @ address 5: \"dat 7\" can be reached as code, but 007 is an undefined instruction",
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_on_data_that_decodes_to_io() {
+        let warnings = lint_src(
+            "
+            hlt
+            dat 901
+            dat 100, 899, 903, 999
+            dat 952",
+        );
+
+        assert_eq!(
+            warnings,
+            [
+                "warning @ address 1: \"dat 901\" would run as \"inp\" if executed",
+                "warning @ address 6: \"dat 952\" would run as \"out 5\" if executed",
+            ]
+        );
+    }
+}
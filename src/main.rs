@@ -1,49 +1,562 @@
 use std::error::Error;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
-use lnc::cli;
+use lnc::cli::{self, ColorChoice, TableStyle};
+use lnc::interpreter::{BrpMode, MemSize};
 
+/// Assembler, interpreter, test runner and debugger for Little Man Computer
+/// programs. `lnc <PATH>` is short for `lnc run <PATH>`.
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// when to color the output: only on a terminal without NO_COLOR set, always or never
+    #[arg(long, global = true, value_enum, default_value_t)]
+    color: ColorChoice,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// assemble and run a program
+    Run(RunArgs),
+
+    /// run the tests in a program
+    Test {
+        /// path to .lmn source code file
+        path: PathBuf,
+
+        /// only run tests whose name contains the given string
+        #[arg(long, value_name = "SUBSTRING")]
+        filter: Option<String>,
+
+        /// also run the tests defined in this file
+        #[arg(long, value_name = "PATH")]
+        tests_file: Option<PathBuf>,
+
+        #[command(flatten)]
+        brp: BrpArgs,
+
+        #[command(flatten)]
+        style: StyleArgs,
+
+        /// print the memory cells each failing test changed from their assembled values
+        #[arg(long)]
+        show_mem_diff: bool,
+
+        /// print the results as JUnit XML instead of logging the tests and printing a table
+        #[arg(long, conflicts_with_all = ["filter", "tests_file", "strict_brp", "show_mem_diff", "watch"])]
+        junit: bool,
+
+        /// re-run the tests whenever the file changes
+        #[arg(short, long)]
+        watch: bool,
+    },
+
+    /// test (or run) each program in a file, starting from its `=== name ===` line
+    Sections {
+        /// path to a file of programs, each after a line like `=== name ===`
+        path: PathBuf,
+
+        /// run each program instead of its tests
+        #[arg(long)]
+        run: bool,
+
+        /// with --run, feed this value to each program (repeatable)
+        #[arg(long = "input", value_name = "VALUE", requires = "run")]
+        inputs: Vec<usize>,
+
+        #[command(flatten)]
+        brp: BrpArgs,
+
+        #[command(flatten)]
+        style: StyleArgs,
+    },
+
+    /// assemble a program and report any errors and warnings without running it
+    Check {
+        /// path to .lmn source code file
+        path: PathBuf,
+
+        /// make a `dat` that can be run as code but isn't a valid instruction an error
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// step through a program, showing memory and registers at each prompt
+    Debug {
+        /// path to .lmn source code file
+        path: PathBuf,
+
+        /// treat the file as up to 100 whitespace-separated cell values
+        #[arg(long)]
+        from_mem: bool,
+
+        #[command(flatten)]
+        brp: BrpArgs,
+
+        #[command(flatten)]
+        style: StyleArgs,
+    },
+
+    /// print the instruction in each memory cell of a program
+    Disasm {
+        /// path to .lmn source code file
+        path: PathBuf,
+
+        #[command(flatten)]
+        image: ImageArgs,
+
+        #[command(flatten)]
+        style: StyleArgs,
+    },
+
+    /// print the control-flow graph of a program in Graphviz DOT format
+    Cfg {
+        /// path to .lmn source code file
+        path: PathBuf,
+    },
+
+    /// list each label with the addresses of the instructions that refer to it
+    Xref {
+        /// path to .lmn source code file
+        path: PathBuf,
+
+        #[command(flatten)]
+        style: StyleArgs,
+    },
+
+    /// assemble a program and write its binary image to a file
+    Assemble {
+        /// path to .lmn source code file
+        path: PathBuf,
+
+        /// where to write the assembled program
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+
+        /// write three-digit cell values as text (see `--from-mem`) instead of a binary image
+        #[arg(long)]
+        text: bool,
+    },
+
+    /// start an interactive session which executes instructions as they are entered
+    Repl {
+        #[command(flatten)]
+        style: StyleArgs,
+    },
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
     /// path to .lmn source code file
-    path: PathBuf,
+    #[arg(required = true)]
+    path: Option<PathBuf>,
+
+    #[command(flatten)]
+    image: ImageArgs,
 
-    /// run tests
+    /// print all outputs on one line once the program halts
     #[arg(short, long)]
-    test: bool,
+    buffered: bool,
+
+    /// print output values of 500 and above as negative numbers (value - 1000)
+    #[arg(long)]
+    signed_out: bool,
+
+    /// how to print the outputs in the summary once the program halts
+    #[arg(long, value_enum, default_value_t)]
+    out_format: cli::OutFormat,
+
+    /// wait for enter to be pressed after each output value
+    #[arg(long)]
+    pause_on_output: bool,
+
+    /// feed this value to the program before asking for inputs interactively (repeatable)
+    #[arg(long = "input", value_name = "VALUE")]
+    inputs: Vec<usize>,
+
+    /// ask for each input with this message instead of "Enter input value: "
+    #[arg(long, value_name = "TEXT")]
+    prompt: Option<String>,
+
+    /// describe each executed instruction in plain English instead of logging it
+    #[arg(long)]
+    explain: bool,
+
+    /// don't log each instruction as it runs, only inputs, outputs and the summary
+    #[arg(short, long, conflicts_with = "explain")]
+    quiet: bool,
+
+    /// don't print the summary once the program halts (add -q to see only inputs and outputs)
+    #[arg(long)]
+    no_summary: bool,
+
+    /// print the addresses that were executed most often once the program halts
+    #[arg(long)]
+    profile: bool,
+
+    /// log every write to this address with its old and new values (repeatable)
+    #[arg(long = "watch-addr", value_name = "ADDR")]
+    watch_addrs: Vec<usize>,
+
+    /// write VALUE into memory at ADDR before the program starts (repeatable)
+    #[arg(long, value_name = "ADDR=VALUE", value_parser = cli::parse_data_arg, conflicts_with_all = ["bin", "from_mem"])]
+    data: Vec<(usize, usize)>,
+
+    /// add the time taken (not counting waiting for inputs) and instructions per second to the summary
+    #[arg(long)]
+    time: bool,
+
+    /// refuse to run if a `dat` that can be run as code isn't a valid instruction
+    #[arg(long, conflicts_with_all = ["bin", "from_mem", "mem_size"])]
+    strict: bool,
+
+    /// print the final contents of memory once the program halts
+    #[arg(long)]
+    dump_mem: bool,
+
+    /// write an execution trace (CSV) to the given file
+    #[arg(long, value_name = "PATH")]
+    trace: Option<PathBuf>,
+
+    /// make a `sto` into an instruction (rather than a `dat` or `var`) a runtime error
+    #[arg(long)]
+    protect_code: bool,
 
-    /// run debugger
+    /// make loading from a cell outside the program that was never stored to a runtime error
+    #[arg(long)]
+    check_uninit: bool,
+
+    /// warn the first time each branch jumps past the end of the program
+    #[arg(long)]
+    check_branches: bool,
+
+    /// run with 1000 memory cells of four-digit words instead of classic LMC's 100
+    #[arg(long, value_enum, value_name = "CELLS", default_value_t, conflicts_with_all = ["bin", "from_mem", "trace", "signed_out"])]
+    mem_size: MemSize,
+
+    /// stop with an error after this many instructions [default: 100000]
+    #[arg(long, value_name = "N")]
+    max_steps: Option<usize>,
+
+    #[command(flatten)]
+    brp: BrpArgs,
+
+    /// re-run the program whenever the file changes
     #[arg(short, long)]
-    debug: bool,
+    watch: bool,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+#[derive(clap::Args)]
+struct ImageArgs {
+    /// treat the file as a binary image (see `lnc assemble`)
+    #[arg(long, conflicts_with = "from_mem")]
+    bin: bool,
 
-    let source = fs::read_to_string(args.path)?;
+    /// treat the file as up to 100 whitespace-separated cell values
+    #[arg(long)]
+    from_mem: bool,
+}
+
+#[derive(clap::Args)]
+struct BrpArgs {
+    /// only take `brp` branches when the accumulator is strictly positive (not zero)
+    #[arg(long)]
+    strict_brp: bool,
+}
 
-    if args.test {
-        if let Err(e) = cli::run_tests(&source) {
-            println!("{e}");
+#[derive(clap::Args)]
+struct StyleArgs {
+    /// draw tables with plain ASCII characters instead of box-drawing ones
+    #[arg(long)]
+    ascii: bool,
+}
+
+impl StyleArgs {
+    fn style(&self) -> TableStyle {
+        if self.ascii {
+            TableStyle::Ascii
+        } else {
+            TableStyle::Sharp
         }
+    }
+}
 
-        return Ok(());
+impl BrpArgs {
+    fn mode(&self) -> BrpMode {
+        if self.strict_brp {
+            BrpMode::Positive
+        } else {
+            BrpMode::ZeroOrPositive
+        }
     }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    let color = args.color;
+    let command = args.command.unwrap_or(Command::Run(args.run));
 
-    if args.debug {
-        if let Err(e) = cli::run_debugger(&source) {
-            println!("{e}");
+    let res = match command {
+        Command::Run(run_args) => return run(run_args),
+        Command::Test {
+            path, junit: true, ..
+        } => match cli::run_tests_junit(&fs::read_to_string(path)?) {
+            Ok((xml, passed)) => {
+                print!("{xml}");
+                if !passed {
+                    process::exit(1);
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                println!("{e}");
+                process::exit(1);
+            }
+        },
+        Command::Test {
+            path,
+            filter,
+            tests_file,
+            brp,
+            style,
+            show_mem_diff,
+            watch: true,
+            ..
+        } => watch(&path, tests_file.as_deref(), |source| {
+            let res = test(
+                source,
+                tests_file.as_deref(),
+                filter.as_deref(),
+                brp.mode(),
+                style.style(),
+                show_mem_diff,
+            );
+            if let Err(e) = res {
+                println!("{e}");
+            }
+        }),
+        Command::Test {
+            path,
+            filter,
+            tests_file,
+            brp,
+            style,
+            show_mem_diff,
+            ..
+        } => {
+            let res = test(
+                &fs::read_to_string(path)?,
+                tests_file.as_deref(),
+                filter.as_deref(),
+                brp.mode(),
+                style.style(),
+                show_mem_diff,
+            );
+
+            // exit non-zero if any test fails, so scripts and CI can detect it
+            match res {
+                Ok(true) => return Ok(()),
+                Ok(false) => process::exit(1),
+                Err(e) => {
+                    println!("{e}");
+                    process::exit(1);
+                }
+            }
         }
+        Command::Sections {
+            path,
+            run,
+            inputs,
+            brp,
+            style,
+        } => {
+            let res = cli::run_sections(
+                &fs::read_to_string(path)?,
+                run,
+                &inputs,
+                brp.mode(),
+                style.style(),
+            );
 
-        return Ok(());
-    }
+            match res {
+                Ok(true) => return Ok(()),
+                Ok(false) => process::exit(1),
+                Err(e) => {
+                    println!("{e}");
+                    process::exit(1);
+                }
+            }
+        }
+        Command::Check { path, strict } => {
+            if let Err(e) = cli::check(&fs::read_to_string(path)?, strict) {
+                println!("{e}");
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        Command::Debug {
+            path,
+            from_mem: true,
+            brp,
+            style,
+        } => cli::debug_mem(&fs::read_to_string(path)?, brp.mode(), style.style(), color),
+        Command::Debug {
+            path, brp, style, ..
+        } => cli::run_debugger(&fs::read_to_string(path)?, brp.mode(), style.style(), color),
+        Command::Disasm { path, image, style } if image.bin => {
+            cli::disasm_bin(&fs::read(path)?, style.style())
+        }
+        Command::Disasm { path, image, style } if image.from_mem => {
+            cli::disasm_mem(&fs::read_to_string(path)?, style.style())
+        }
+        Command::Disasm { path, style, .. } => {
+            cli::disasm(&fs::read_to_string(path)?, style.style())
+        }
+        Command::Cfg { path } => cli::print_cfg(&fs::read_to_string(path)?),
+        Command::Xref { path, style } => cli::xref(&fs::read_to_string(path)?, style.style()),
+        Command::Assemble { path, output, text } => {
+            cli::assemble_to(&fs::read_to_string(path)?, &output, text)
+        }
+        Command::Repl { style } => cli::run_repl(style.style()),
+    };
 
-    if let Err(e) = cli::run(&source) {
+    if let Err(e) = res {
         println!("{e}");
     }
 
     Ok(())
 }
+
+fn run(args: RunArgs) -> Result<(), Box<dyn Error>> {
+    let Some(path) = &args.path else {
+        unreachable!("clap requires a path to run");
+    };
+
+    let options = cli::RunOptions {
+        buffered_output: args.buffered,
+        signed_output: args.signed_out,
+        out_format: args.out_format,
+        trace: args.trace,
+        pause_on_output: args.pause_on_output,
+        brp_mode: args.brp.mode(),
+        max_steps: args.max_steps,
+        protect_code: args.protect_code,
+        check_uninit: args.check_uninit,
+        check_branches: args.check_branches,
+        explain: args.explain,
+        quiet: args.quiet,
+        prompt: args.prompt,
+        mem_size: args.mem_size,
+        inputs: args.inputs,
+        no_summary: args.no_summary,
+        profile: args.profile,
+        watch_addrs: args.watch_addrs,
+        data: args.data,
+        time: args.time,
+        strict: args.strict,
+    };
+
+    let res = if args.image.bin {
+        cli::run_bin(&fs::read(path)?, &options)
+    } else if args.image.from_mem {
+        cli::run_mem(&fs::read_to_string(path)?, &options)
+    } else if args.watch {
+        watch(path, None, |source| match cli::run(source, &options) {
+            Ok(mem) => print_mem(&mem, args.dump_mem),
+            Err(e) => println!("{e}"),
+        })
+    } else {
+        cli::run(&fs::read_to_string(path)?, &options)
+    };
+
+    match res {
+        Ok(mem) => print_mem(&mem, args.dump_mem),
+        Err(e) => println!("{e}"),
+    }
+
+    Ok(())
+}
+
+// The tests file is re-read on every run so it can be edited while watching.
+fn test(
+    source: &str,
+    tests_file: Option<&Path>,
+    filter: Option<&str>,
+    brp_mode: BrpMode,
+    style: TableStyle,
+    show_mem_diff: bool,
+) -> Result<bool, String> {
+    let Some(tests_file) = tests_file else {
+        return cli::run_tests(source, filter, brp_mode, style, show_mem_diff);
+    };
+
+    let tests_source = fs::read_to_string(tests_file)
+        .map_err(|e| format!("error reading {}: {e}", tests_file.display()))?;
+
+    cli::run_tests_with_file(
+        source,
+        &tests_source,
+        filter,
+        brp_mode,
+        style,
+        show_mem_diff,
+    )
+}
+
+fn print_mem(mem: &[usize], dump_mem: bool) {
+    if dump_mem {
+        println!("\n--- memory ---");
+        println!("{}", cli::format_mem_grid(mem));
+    }
+}
+
+// Runs `run_source` on the contents of `path` again whenever it, or the file
+// `also`, changes.
+fn watch<F: Fn(&str)>(path: &Path, also: Option<&Path>, run_source: F) -> ! {
+    let mut last_modified: Option<(SystemTime, Option<SystemTime>)> = None;
+    let mut waiting = false;
+
+    loop {
+        let also_modified = also.and_then(|p| fs::metadata(p).and_then(|m| m.modified()).ok());
+
+        // the file can briefly disappear while an editor is saving it
+        match fs::metadata(path).and_then(|m| m.modified()) {
+            Ok(modified) if Some((modified, also_modified)) != last_modified => {
+                if let Ok(source) = fs::read_to_string(path) {
+                    last_modified = Some((modified, also_modified));
+                    waiting = false;
+
+                    // clear the screen and move the cursor to the top left
+                    print!("\x1B[2J\x1B[1;1H");
+                    println!("--- {} ---", path.display());
+                    run_source(&source);
+                    println!("\n--- watching for changes ---");
+                    let _ = io::stdout().flush();
+                }
+            }
+            Ok(_) => (),
+            Err(_) => {
+                if !waiting {
+                    println!("waiting for {} to exist...", path.display());
+                    waiting = true;
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(500));
+    }
+}
@@ -1,13 +1,22 @@
+// The partial ParseInfo is returned alongside errors so that later stages can
+// still run and report their own errors.
+#![allow(clippy::result_large_err)]
+
 use std::collections::HashMap;
 use std::iter::Peekable;
 use std::slice::Iter;
 
 use crate::lex::{Token, TokenKind};
+use crate::LNCError;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Address {
     Symbolic(String),
     Numeric(usize),
+    // label+n, resolved (and range checked) by the assembler
+    Offset(String, usize),
+    // `.+n` or `.-n`, relative to the address of the instruction using it
+    Relative(isize),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -18,7 +27,11 @@ pub enum Instruction {
     Subtract(Address),
     Input,
     Output,
+    // `inp`/`out` with an explicit device number
+    InputDevice(usize),
+    OutputDevice(usize),
     Halt,
+    Nop,
     BranchZero(Address),
     BranchPositive(Address),
     BranchAlways(Address),
@@ -30,6 +43,12 @@ pub struct LNCTest {
     pub name: String,
     pub inputs: Vec<usize>,
     pub outputs: Vec<usize>,
+    // the test passes only if the program fails with a runtime error
+    pub expect_error: bool,
+    // `mem[addr]=value` clauses: (addr, value) pairs checked once the program halts
+    pub mem_checks: Vec<(usize, usize)>,
+    // a trailing `skip` lists the test as skipped instead of running it
+    pub skipped: bool,
 }
 
 #[derive(Debug)]
@@ -37,45 +56,97 @@ pub struct ParseInfo {
     pub instructions: Vec<Instruction>,
     pub label_map: HashMap<String, usize>,
     pub tests: Vec<LNCTest>,
+    // the source line of each entry in `instructions`
+    pub lines: Vec<usize>,
+    // variables declared with `var`, allocated after the program by the assembler
+    pub vars: Vec<String>,
+    pub var_lines: Vec<usize>,
+    // the values of `ldi`, `addi` and `subi`, each allocated once after the vars
+    pub consts: Vec<usize>,
+    pub const_lines: Vec<usize>,
 }
 
 impl ParseInfo {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             instructions: vec![],
             label_map: HashMap::new(),
             tests: vec![],
+            lines: vec![],
+            vars: vec![],
+            var_lines: vec![],
+            consts: vec![],
+            const_lines: vec![],
         }
     }
 }
 
+// `var` is only a keyword after a variable name, so it can still be a label.
+fn is_var(word: &str) -> bool {
+    word.eq_ignore_ascii_case("var")
+}
+
+// parse errors are tagged with the line of the token that caused them
+type ParseResult<T> = Result<T, (usize, String)>;
+
+// Where `call` saves the accumulator. Like the `<subroutine>.ret` labels of `ret`,
+// the `.` means it can't clash with a name in the source.
+pub const CALL_ACC: &str = "call.acc";
+
+pub fn const_label(val: usize) -> String {
+    format!("const.{val}")
+}
+
 struct Parser<'a> {
     it: Peekable<Iter<'a, Token>>,
+    line: usize,
     paddr: usize,
     info: ParseInfo,
-    errors: Vec<String>,
+    errors: Vec<LNCError>,
+    // an error found at the end of a line may already have consumed its newline
+    after_newline: bool,
+    // the size of memory, which limits addresses and `dat` values
+    cells: usize,
+    // the subroutine and line of each `call`, and the address of each `ret`
+    calls: Vec<(String, usize)>,
+    rets: Vec<usize>,
 }
 
 impl<'a> Parser<'a> {
-    fn new(tokens: &'a [Token]) -> Self {
+    fn new(tokens: &'a [Token], cells: usize) -> Self {
         Self {
             it: tokens.iter().peekable(),
+            line: 1,
             paddr: 0,
             info: ParseInfo::new(),
             errors: vec![],
+            after_newline: false,
+            cells,
+            calls: vec![],
+            rets: vec![],
         }
     }
 
     fn add_err_msg(&mut self, line: usize, msg: String) {
-        self.errors.push(format!("error @ line {}: {}", line, msg));
+        self.errors.push(LNCError::Parse { line, msg });
     }
 
-    fn make_instructions(mut self) -> Result<ParseInfo, (ParseInfo, String)> {
+    fn make_instructions(mut self) -> Result<ParseInfo, (ParseInfo, LNCError)> {
         while let Some(token) = self.consume() {
+            let line = token.line;
             let res = match token.kind {
                 TokenKind::LabelDef(s) => {
-                    self.info.label_map.insert(s, self.paddr);
-                    Ok(())
+                    if let Some(addr) = self.info.label_map.get(&s) {
+                        Err((
+                            line,
+                            format!("label '{s}' already defined at address {addr}"),
+                        ))
+                    } else if self.info.vars.contains(&s) {
+                        Err((line, format!("label '{s}' already defined as a variable")))
+                    } else {
+                        self.info.label_map.insert(s, self.paddr);
+                        Ok(())
+                    }
                 }
                 TokenKind::Load
                 | TokenKind::Store
@@ -84,38 +155,97 @@ impl<'a> Parser<'a> {
                 | TokenKind::BranchZero
                 | TokenKind::BranchPositive
                 | TokenKind::BranchAlways => self.ins_with_addr(&token),
-                TokenKind::Input | TokenKind::Output | TokenKind::Halt => {
+                TokenKind::Input | TokenKind::Output | TokenKind::Halt | TokenKind::Nop => {
                     self.ins_without_addr(&token)
                 }
-                TokenKind::Data => self.data(),
+                TokenKind::Data => self.data(line),
+                TokenKind::LoadImmediate
+                | TokenKind::AddImmediate
+                | TokenKind::SubtractImmediate => self.ins_with_value(&token),
+                TokenKind::Call => self.call(line),
+                TokenKind::Return => self.ret(line),
                 TokenKind::NewLine => Ok(()),
                 TokenKind::Eof => break,
-                TokenKind::Number(n) => Err(format!(
-                    "found number ({n}) instead of instruction/label def"
-                )),
-                TokenKind::Label(s) => Err(format!(
-                    "found label \"{s}\" instead of instruction/label def"
+                TokenKind::Number(n) | TokenKind::Immediate(n) => Err((
+                    line,
+                    format!("found number ({n}) instead of instruction/label def"),
                 )),
+                TokenKind::Label(s) => {
+                    if self
+                        .peek()
+                        .is_some_and(|t| matches!(&t.kind, TokenKind::Label(v) if is_var(v)))
+                    {
+                        self.consume();
+                        self.var(line, s)
+                    } else if is_var(&s) {
+                        Err((line, "expected a variable name before 'var'".into()))
+                    } else {
+                        Err((
+                            line,
+                            format!("found label \"{s}\" instead of instruction/label def"),
+                        ))
+                    }
+                }
                 TokenKind::TestName(s) => self.lnc_test(s),
-                TokenKind::OpenSquareBracket => Err("unexpected bracket '['".into()),
-                TokenKind::CloseSquareBracket => Err("unexpected bracket ']'".into()),
-                TokenKind::Comma => Err("unexpected comma ','".into()),
+                TokenKind::OpenSquareBracket => Err((line, "unexpected bracket '['".into())),
+                TokenKind::CloseSquareBracket => Err((line, "unexpected bracket ']'".into())),
+                TokenKind::Comma => Err((line, "unexpected comma ','".into())),
+                TokenKind::Bang => Err((line, "unexpected '!'".into())),
+                TokenKind::Plus => Err((line, "unexpected '+'".into())),
+                TokenKind::Equals => Err((line, "unexpected '='".into())),
+                TokenKind::Minus => Err((line, "unexpected '-'".into())),
+                TokenKind::Here => Err((line, "unexpected '.'".into())),
             };
 
-            if let Err(e) = res {
-                self.add_err_msg(token.line, e);
+            if let Err((line, e)) = res {
+                self.add_err_msg(line, e);
                 self.sync();
             }
         }
 
+        self.resolve_rets();
+
         if self.errors.is_empty() {
             Ok(self.info)
         } else {
-            Err((self.info, self.errors.join("\n")))
+            Err((self.info, LNCError::from_vec(self.errors)))
+        }
+    }
+
+    // A tests file holds nothing but test definitions.
+    fn make_tests(mut self) -> Result<Vec<LNCTest>, LNCError> {
+        while let Some(token) = self.consume() {
+            let res = match token.kind {
+                TokenKind::TestName(s) => self.lnc_test(s),
+                TokenKind::NewLine => Ok(()),
+                TokenKind::Eof => break,
+                _ => Err((
+                    token.line,
+                    format!(
+                        "expected a test definition but found {}",
+                        token.kind.describe()
+                    ),
+                )),
+            };
+
+            if let Err((line, e)) = res {
+                self.add_err_msg(line, e);
+                self.sync();
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(self.info.tests)
+        } else {
+            Err(LNCError::from_vec(self.errors))
         }
     }
 
     fn sync(&mut self) {
+        if self.after_newline {
+            return;
+        }
+
         while let Some(token) = self.peek() {
             if matches!(token.kind, TokenKind::NewLine | TokenKind::Eof) {
                 break;
@@ -125,25 +255,43 @@ impl<'a> Parser<'a> {
     }
 
     fn consume(&mut self) -> Option<Token> {
-        self.it.next().cloned()
+        let token = self.it.next().cloned();
+
+        if let Some(t) = &token {
+            self.line = t.line;
+            self.after_newline = t.kind == TokenKind::NewLine;
+        }
+
+        token
     }
 
     fn peek(&mut self) -> Option<&Token> {
         self.it.peek().copied()
     }
 
-    fn add_ins(&mut self, ins: Instruction) {
+    fn add_ins(&mut self, line: usize, ins: Instruction) {
         self.info.instructions.push(ins);
+        self.info.lines.push(line);
         self.paddr += 1;
     }
 
-    fn check_next(&mut self, kind: TokenKind) -> Result<(), String> {
+    fn check_next(&mut self, kind: TokenKind) -> ParseResult<()> {
         if let Some(next) = self.peek() {
             if next.kind != kind {
-                return Err(format!("expected {:?}: found {:?}", kind, next.kind));
+                return Err((
+                    next.line,
+                    format!(
+                        "expected {} but found {}",
+                        kind.describe(),
+                        next.kind.describe()
+                    ),
+                ));
             }
         } else {
-            return Err(format!("unexpected EOF: expected {:?}", kind));
+            return Err((
+                self.line,
+                format!("unexpected EOF: expected {}", kind.describe()),
+            ));
         }
 
         self.consume();
@@ -151,16 +299,19 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
-    fn check_newline(&mut self) -> Result<(), String> {
+    fn check_newline(&mut self) -> ParseResult<()> {
         if let Some(nl_token) = self.peek() {
             if !matches!(nl_token.kind, TokenKind::NewLine | TokenKind::Eof) {
-                return Err(format!(
-                    "invalid token {:?}: expected end of line",
-                    nl_token
+                return Err((
+                    nl_token.line,
+                    format!(
+                        "expected end of line but found {}",
+                        nl_token.kind.describe()
+                    ),
                 ));
             }
         } else {
-            return Err("unexpected EOF: expected address".to_owned());
+            return Err((self.line, "unexpected EOF: expected address".to_owned()));
         }
 
         self.consume();
@@ -168,73 +319,434 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
-    fn ins_with_addr(&mut self, token: &Token) -> Result<(), String> {
+    fn ins_with_addr(&mut self, token: &Token) -> ParseResult<()> {
         let addr = if let Some(addr_token) = self.consume() {
+            let line = addr_token.line;
             match addr_token.kind {
                 TokenKind::Number(n) => {
-                    if n >= 100 {
-                        return Err(format!("invalid address {}: too large", n));
+                    if n >= self.cells {
+                        return Err((line, format!("invalid address {}: too large", n)));
                     }
                     Address::Numeric(n)
                 }
                 TokenKind::Label(s) => Address::Symbolic(s),
-                _ => return Err(format!("invalid token {:?}: expected address", addr_token)),
+                TokenKind::Here => Address::Relative(0),
+                TokenKind::Immediate(n) => {
+                    return Err((
+                        line,
+                        format!("#{n} is a value, not an address (use {n} for address {n})"),
+                    ))
+                }
+                _ => {
+                    return Err((
+                        line,
+                        format!(
+                            "expected an address but found {}",
+                            addr_token.kind.describe()
+                        ),
+                    ))
+                }
             }
         } else {
-            return Err("unexpected EOF: expected address".to_owned());
+            return Err((self.line, "unexpected EOF: expected address".to_owned()));
+        };
+
+        let addr = match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Plus) => {
+                self.consume();
+                self.offset(addr)?
+            }
+            Some(TokenKind::Minus) if matches!(addr, Address::Relative(_)) => {
+                self.consume();
+                self.backward_offset()?
+            }
+            _ => addr,
         };
 
         self.check_newline()?;
 
         match token.kind {
-            TokenKind::Load => self.add_ins(Instruction::Load(addr)),
-            TokenKind::Store => self.add_ins(Instruction::Store(addr)),
-            TokenKind::Add => self.add_ins(Instruction::Add(addr)),
-            TokenKind::Subtract => self.add_ins(Instruction::Subtract(addr)),
-            TokenKind::BranchZero => self.add_ins(Instruction::BranchZero(addr)),
-            TokenKind::BranchPositive => self.add_ins(Instruction::BranchPositive(addr)),
-            TokenKind::BranchAlways => self.add_ins(Instruction::BranchAlways(addr)),
+            TokenKind::Load => self.add_ins(token.line, Instruction::Load(addr)),
+            TokenKind::Store => self.add_ins(token.line, Instruction::Store(addr)),
+            TokenKind::Add => self.add_ins(token.line, Instruction::Add(addr)),
+            TokenKind::Subtract => self.add_ins(token.line, Instruction::Subtract(addr)),
+            TokenKind::BranchZero => self.add_ins(token.line, Instruction::BranchZero(addr)),
+            TokenKind::BranchPositive => {
+                self.add_ins(token.line, Instruction::BranchPositive(addr))
+            }
+            TokenKind::BranchAlways => self.add_ins(token.line, Instruction::BranchAlways(addr)),
             _ => unreachable!(),
         }
 
         Ok(())
     }
 
-    fn ins_without_addr(&mut self, token: &Token) -> Result<(), String> {
+    // `addi 5` is `add` from a hidden `dat 5` cell, labelled `const.5`.
+    fn ins_with_value(&mut self, token: &Token) -> ParseResult<()> {
+        let val = self.data_value()?;
+        self.check_newline()?;
+
+        if !self.info.consts.contains(&val) {
+            self.info.consts.push(val);
+            self.info.const_lines.push(token.line);
+        }
+
+        let addr = Address::Symbolic(const_label(val));
+        match token.kind {
+            TokenKind::LoadImmediate => self.add_ins(token.line, Instruction::Load(addr)),
+            TokenKind::AddImmediate => self.add_ins(token.line, Instruction::Add(addr)),
+            TokenKind::SubtractImmediate => self.add_ins(token.line, Instruction::Subtract(addr)),
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    fn offset(&mut self, addr: Address) -> ParseResult<Address> {
+        let Some(Token { kind, line }) = self.consume() else {
+            return Err((self.line, "unexpected EOF: expected offset".to_owned()));
+        };
+
+        let TokenKind::Number(offset) = kind else {
+            return Err((
+                line,
+                format!("expected an offset but found {}", kind.describe()),
+            ));
+        };
+
+        match addr {
+            Address::Numeric(n) => match n.checked_add(offset) {
+                Some(addr) if addr < self.cells => Ok(Address::Numeric(addr)),
+                Some(addr) => Err((
+                    line,
+                    format!("invalid address {n}+{offset}: {addr} is too large"),
+                )),
+                None => Err((line, format!("invalid address {n}+{offset}: too large"))),
+            },
+            Address::Symbolic(label) => Ok(Address::Offset(label, offset)),
+            // no larger offset can stay inside memory
+            Address::Relative(_) if offset >= self.cells => Err((
+                line,
+                format!(
+                    "offset .+{offset} is out of range: must be below {}",
+                    self.cells
+                ),
+            )),
+            Address::Relative(_) => Ok(Address::Relative(offset as isize)),
+            Address::Offset(..) => unreachable!(),
+        }
+    }
+
+    // the `n` in `.-n`
+    fn backward_offset(&mut self) -> ParseResult<Address> {
+        match self.consume() {
+            Some(Token {
+                kind: TokenKind::Number(n),
+                line,
+            }) if n >= self.cells => Err((
+                line,
+                format!("offset .-{n} is out of range: must be below {}", self.cells),
+            )),
+            Some(Token {
+                kind: TokenKind::Number(n),
+                ..
+            }) => Ok(Address::Relative(-(n as isize))),
+            Some(Token { kind, line }) => Err((
+                line,
+                format!("expected an offset but found {}", kind.describe()),
+            )),
+            None => Err((self.line, "unexpected EOF: expected offset".to_owned())),
+        }
+    }
+
+    fn ins_without_addr(&mut self, token: &Token) -> ParseResult<()> {
+        if matches!(token.kind, TokenKind::Input | TokenKind::Output) {
+            if let Some(device) = self.device()? {
+                self.check_newline()?;
+
+                match token.kind {
+                    TokenKind::Input => self.add_ins(token.line, Instruction::InputDevice(device)),
+                    TokenKind::Output => {
+                        self.add_ins(token.line, Instruction::OutputDevice(device))
+                    }
+                    _ => unreachable!(),
+                }
+
+                return Ok(());
+            }
+        }
+
         self.check_newline()?;
 
         match token.kind {
-            TokenKind::Input => self.add_ins(Instruction::Input),
-            TokenKind::Output => self.add_ins(Instruction::Output),
-            TokenKind::Halt => self.add_ins(Instruction::Halt),
+            TokenKind::Input => self.add_ins(token.line, Instruction::Input),
+            TokenKind::Output => self.add_ins(token.line, Instruction::Output),
+            TokenKind::Halt => self.add_ins(token.line, Instruction::Halt),
+            TokenKind::Nop => self.add_ins(token.line, Instruction::Nop),
             _ => unreachable!(),
         }
 
         Ok(())
     }
 
-    fn data(&mut self) -> Result<(), String> {
-        let num = if let Some(num_token) = self.consume() {
-            if let TokenKind::Number(n) = num_token.kind {
-                if n >= 1000 {
-                    return Err(format!("invalid data {}: too large", n));
+    fn device(&mut self) -> ParseResult<Option<usize>> {
+        let Some(&Token {
+            kind: TokenKind::Number(n),
+            line,
+        }) = self.peek()
+        else {
+            return Ok(None);
+        };
+
+        if n > 9 {
+            return Err((line, format!("invalid device {n}: must be 0-9")));
+        }
+
+        self.consume();
+
+        Ok(Some(n))
+    }
+
+    fn data(&mut self, line: usize) -> ParseResult<()> {
+        // a bare `dat` is `dat 0`
+        if matches!(
+            self.peek(),
+            Some(Token {
+                kind: TokenKind::NewLine | TokenKind::Eof,
+                ..
+            })
+        ) {
+            self.check_newline()?;
+            self.add_ins(line, Instruction::Data(0));
+            return Ok(());
+        }
+
+        let mut nums = vec![self.data_value()?];
+
+        while matches!(
+            self.peek(),
+            Some(Token {
+                kind: TokenKind::Comma,
+                ..
+            })
+        ) {
+            self.consume();
+            nums.push(self.data_value()?);
+        }
+
+        self.check_newline()?;
+
+        for num in nums {
+            self.add_ins(line, Instruction::Data(num));
+        }
+
+        Ok(())
+    }
+
+    fn data_value(&mut self) -> ParseResult<usize> {
+        if let Some(num_token) = self.consume() {
+            if let TokenKind::Number(n) | TokenKind::Immediate(n) = num_token.kind {
+                if n >= self.cells * 10 {
+                    return Err((num_token.line, format!("invalid data {}: too large", n)));
                 }
-                n
+                Ok(n)
             } else {
-                return Err(format!("invalid token {:?}: expected number", num_token));
+                Err((
+                    num_token.line,
+                    format!("expected a number but found {}", num_token.kind.describe()),
+                ))
             }
         } else {
-            return Err("io token found".to_owned());
+            Err((self.line, "io token found".to_owned()))
+        }
+    }
+
+    fn var(&mut self, line: usize, name: String) -> ParseResult<()> {
+        self.check_newline()?;
+
+        if let Some(addr) = self.info.label_map.get(&name) {
+            return Err((
+                line,
+                format!("label '{name}' already defined at address {addr}"),
+            ));
+        }
+        if self.info.vars.contains(&name) {
+            return Err((
+                line,
+                format!("label '{name}' already defined as a variable"),
+            ));
+        }
+
+        self.info.vars.push(name);
+        self.info.var_lines.push(line);
+
+        Ok(())
+    }
+
+    // `call f` saves the accumulator, stores a `bra` back to just after the
+    // call into the `ret` of `f`, then restores the accumulator and jumps:
+    //
+    //     sto call.acc
+    //     lda .+4
+    //     sto f.ret
+    //     lda call.acc
+    //     bra f
+    //     dat <bra to the next instruction>
+    fn call(&mut self, line: usize) -> ParseResult<()> {
+        let name = match self.consume() {
+            Some(Token {
+                kind: TokenKind::Label(name),
+                ..
+            }) => name,
+            Some(token) => {
+                return Err((
+                    token.line,
+                    format!(
+                        "expected a subroutine label but found {}",
+                        token.kind.describe()
+                    ),
+                ))
+            }
+            None => return Err((self.line, "unexpected EOF: expected a label".into())),
         };
+        self.check_newline()?;
 
-        self.add_ins(Instruction::Data(num));
+        let return_addr = self.paddr + 6;
+        if return_addr >= self.cells {
+            return Err((line, format!("no room to return to after calling '{name}'")));
+        }
+
+        if !self.info.vars.iter().any(|var| var == CALL_ACC) {
+            self.info.vars.push(CALL_ACC.into());
+            self.info.var_lines.push(line);
+        }
+
+        let acc = || Address::Symbolic(CALL_ACC.into());
+        self.add_ins(line, Instruction::Store(acc()));
+        self.add_ins(line, Instruction::Load(Address::Relative(4)));
+        self.add_ins(
+            line,
+            Instruction::Store(Address::Symbolic(format!("{name}.ret"))),
+        );
+        self.add_ins(line, Instruction::Load(acc()));
+        self.add_ins(
+            line,
+            Instruction::BranchAlways(Address::Symbolic(name.clone())),
+        );
+        self.add_ins(line, Instruction::Data(6 * self.cells + return_addr));
+
+        self.calls.push((name, line));
 
         Ok(())
     }
 
-    fn lnc_test(&mut self, name: String) -> Result<(), String> {
-        let inputs = self.number_list()?;
-        let outputs = self.number_list()?;
+    // Halts until a `call` overwrites it with a branch back to the caller.
+    fn ret(&mut self, line: usize) -> ParseResult<()> {
+        self.check_newline()?;
+
+        self.rets.push(self.paddr);
+        self.add_ins(line, Instruction::Data(0));
+
+        Ok(())
+    }
+
+    // Each `ret` belongs to the nearest subroutine (a label used by `call`)
+    // defined before it, and is labelled `<subroutine>.ret` for the calls.
+    fn resolve_rets(&mut self) {
+        for ret in std::mem::take(&mut self.rets) {
+            let line = self.info.lines[ret];
+            let owner = self
+                .calls
+                .iter()
+                .filter_map(|(name, _)| self.info.label_map.get(name).map(|&addr| (addr, name)))
+                .filter(|&(addr, _)| addr <= ret)
+                .max_by_key(|&(addr, _)| addr)
+                .map(|(_, name)| name.clone());
+
+            let Some(name) = owner else {
+                self.add_err_msg(
+                    line,
+                    "ret is not inside a subroutine (no label used by call comes before it)".into(),
+                );
+                continue;
+            };
+
+            let label = format!("{name}.ret");
+            if let Some(addr) = self.info.label_map.get(&label) {
+                let msg = format!("subroutine '{name}' already returns at address {addr}");
+                self.add_err_msg(line, msg);
+            } else {
+                self.info.label_map.insert(label, ret);
+            }
+        }
+
+        for (name, line) in std::mem::take(&mut self.calls) {
+            let label = format!("{name}.ret");
+            if self.info.label_map.contains_key(&label) {
+                continue;
+            }
+
+            // an undefined subroutine is reported once, by the assembler
+            if self.info.label_map.contains_key(&name) {
+                self.add_err_msg(line, format!("subroutine '{name}' has no ret"));
+            }
+            self.info.label_map.insert(label, 0);
+        }
+    }
+
+    fn lnc_test(&mut self, name: String) -> ParseResult<()> {
+        let line = self.line;
+        if self.info.tests.iter().any(|test| test.name == name) {
+            return Err((line, format!("duplicate test name '{name}'")));
+        }
+
+        if let Some(&Token {
+            kind: TokenKind::NewLine | TokenKind::Eof,
+            line,
+        }) = self.peek()
+        {
+            return Err((
+                line,
+                format!(
+                    "test '{name}' needs an output list, e.g. .{name} [3] or .{name} [1, 2] [3]"
+                ),
+            ));
+        }
+
+        let first = self.number_list()?;
+
+        let next_kind = self.peek().map(|t| &t.kind);
+        let expect_error = matches!(next_kind, Some(TokenKind::Bang));
+
+        // a single list is always the outputs of a test without inputs
+        let (inputs, outputs) = match next_kind {
+            Some(TokenKind::NewLine | TokenKind::Eof) => (vec![], first),
+            Some(TokenKind::Label(l)) if l == "mem" || l == "skip" => (vec![], first),
+            Some(TokenKind::Bang) => {
+                self.consume();
+                (first, vec![])
+            }
+            _ => (first, self.number_list()?),
+        };
+
+        let mut mem_checks = vec![];
+        while let Some(Token {
+            kind: TokenKind::Label(l),
+            ..
+        }) = self.peek()
+        {
+            if l != "mem" || expect_error {
+                break;
+            }
+            self.consume();
+            mem_checks.push(self.mem_check()?);
+        }
+
+        let skipped =
+            matches!(self.peek(), Some(Token { kind: TokenKind::Label(l), .. }) if l == "skip");
+        if skipped {
+            self.consume();
+        }
 
         self.check_newline()?;
 
@@ -242,37 +754,80 @@ impl<'a> Parser<'a> {
             name,
             inputs,
             outputs,
+            expect_error,
+            mem_checks,
+            skipped,
         });
 
         Ok(())
     }
 
-    fn number_list(&mut self) -> Result<Vec<usize>, String> {
+    // the `[addr]=value` after `mem`
+    fn mem_check(&mut self) -> ParseResult<(usize, usize)> {
+        self.check_next(TokenKind::OpenSquareBracket)?;
+        let addr = self.test_number(100, "address")?;
+        self.check_next(TokenKind::CloseSquareBracket)?;
+        self.check_next(TokenKind::Equals)?;
+        let value = self.test_number(1000, "value")?;
+
+        Ok((addr, value))
+    }
+
+    fn test_number(&mut self, limit: usize, what: &str) -> ParseResult<usize> {
+        match self.consume() {
+            Some(Token {
+                kind: TokenKind::Number(n),
+                line,
+            }) => {
+                if n >= limit {
+                    Err((line, format!("invalid {what} {n}: too large")))
+                } else {
+                    Ok(n)
+                }
+            }
+            Some(token) => Err((
+                token.line,
+                format!("expected {what} but found {}", token.kind.describe()),
+            )),
+            None => Err((self.line, format!("unexpected EOF: expected {what}"))),
+        }
+    }
+
+    fn number_list(&mut self) -> ParseResult<Vec<usize>> {
         self.check_next(TokenKind::OpenSquareBracket)?;
 
         let mut nums = vec![];
         let mut prev_was_num = false;
 
         while let Some(token) = self.peek() {
+            let line = token.line;
             match token.kind {
                 TokenKind::Number(n) => {
                     if prev_was_num {
-                        return Err(format!("expected ',' or ']': found number ({n})"));
+                        return Err((line, format!("expected ',' or ']' but found number {n}")));
                     }
                     if n >= 1000 {
-                        return Err(format!("invalid number {n}: too large"));
+                        return Err((line, format!("invalid number {n}: too large")));
                     }
                     nums.push(n);
                     prev_was_num = true;
                 }
                 TokenKind::Comma => {
                     if !prev_was_num {
-                        return Err("unexpected ','".into());
+                        return Err((line, "unexpected ','".into()));
                     }
                     prev_was_num = false;
                 }
                 TokenKind::CloseSquareBracket => break,
-                _ => return Err(format!("expected number, ',', or ']': found {token:?}")),
+                _ => {
+                    return Err((
+                        line,
+                        format!(
+                            "expected number, ',', or ']' but found {}",
+                            token.kind.describe()
+                        ),
+                    ))
+                }
             }
 
             self.consume();
@@ -284,11 +839,16 @@ impl<'a> Parser<'a> {
     }
 }
 
-pub fn parse(tokens: &[Token]) -> Result<ParseInfo, (ParseInfo, String)> {
-    let parser = Parser::new(tokens);
+// Parses a program for a memory of `cells` cells, see `MemSize`.
+pub fn parse(tokens: &[Token], cells: usize) -> Result<ParseInfo, (ParseInfo, LNCError)> {
+    let parser = Parser::new(tokens, cells);
     parser.make_instructions()
 }
 
+pub fn parse_tests(tokens: &[Token]) -> Result<Vec<LNCTest>, LNCError> {
+    Parser::new(tokens, 100).make_tests()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -298,14 +858,14 @@ mod tests {
         parse_src(source).unwrap().instructions.remove(0)
     }
 
-    fn parse_src(source: &str) -> Result<ParseInfo, (ParseInfo, String)> {
+    fn parse_src(source: &str) -> Result<ParseInfo, (ParseInfo, LNCError)> {
         let tokens = tokenize(source).unwrap();
-        parse(&tokens)
+        parse(&tokens, 100)
     }
 
-    fn get_nlist(source: &str) -> Result<Vec<usize>, String> {
+    fn get_nlist(source: &str) -> ParseResult<Vec<usize>> {
         let tokens = tokenize(source).unwrap();
-        let mut parser = Parser::new(&tokens);
+        let mut parser = Parser::new(&tokens, 100);
         parser.number_list()
     }
 
@@ -314,6 +874,9 @@ mod tests {
             name: name.into(),
             inputs,
             outputs,
+            expect_error: false,
+            mem_checks: vec![],
+            skipped: false,
         }
     }
 
@@ -321,6 +884,26 @@ mod tests {
         parse_src(source).unwrap().tests.remove(0)
     }
 
+    #[test]
+    fn parse_extended_addr() {
+        let tokens = tokenize("lda 999\ndat 9999\nbra 5+990").unwrap();
+        let info = parse(&tokens, 1000).unwrap();
+        assert_eq!(
+            info.instructions,
+            [
+                Instruction::Load(Address::Numeric(999)),
+                Instruction::Data(9999),
+                Instruction::BranchAlways(Address::Numeric(995)),
+            ]
+        );
+
+        for source in ["lda 1000", "dat 10000", "bra 999+1"] {
+            assert!(parse(&tokenize(source).unwrap(), 1000).is_err(), "{source}");
+        }
+        assert!(parse_src("lda 999").is_err());
+        assert!(parse_src("dat 9999").is_err());
+    }
+
     #[test]
     fn parse_with_addr() {
         use Address::Numeric;
@@ -362,16 +945,159 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_immediate() {
+        assert_eq!(single("dat #65"), Instruction::Data(65));
+        assert_eq!(
+            parse_src("dat #1, 2, #3").unwrap().instructions,
+            [
+                Instruction::Data(1),
+                Instruction::Data(2),
+                Instruction::Data(3)
+            ]
+        );
+
+        assert!(parse_src("dat #1000").is_err());
+        assert_eq!(
+            parse_src("lda #5").unwrap_err().1.to_string(),
+            "error @ line 1: #5 is a value, not an address (use 5 for address 5)"
+        );
+        assert!(parse_src("bra #0").is_err());
+        assert!(parse_src("inp #1").is_err());
+        assert!(parse_src("#5").is_err());
+        assert!(parse_src(".t [#1] [1]").is_err());
+    }
+
+    #[test]
+    fn parse_offset_addr() {
+        assert_eq!(
+            single("lda table+2"),
+            Instruction::Load(Address::Offset("table".into(), 2))
+        );
+        assert_eq!(single("sto 10+5"), Instruction::Store(Address::Numeric(15)));
+
+        assert!(parse_src("lda 90+10").is_err());
+        assert_eq!(
+            parse_src("lda 1+18446744073709551615")
+                .unwrap_err()
+                .1
+                .to_string(),
+            "error @ line 1: invalid address 1+18446744073709551615: too large"
+        );
+        assert!(parse_src("lda table+").is_err());
+        assert!(parse_src("lda table+other").is_err());
+        assert!(parse_src("lda table+1+1").is_err());
+        assert!(parse_src("lda +1").is_err());
+        assert!(parse_src("inp+1").is_err());
+    }
+
+    #[test]
+    fn parse_relative_addr() {
+        assert_eq!(
+            single("bra ."),
+            Instruction::BranchAlways(Address::Relative(0))
+        );
+        assert_eq!(
+            single("bra .+2"),
+            Instruction::BranchAlways(Address::Relative(2))
+        );
+        assert_eq!(
+            single("brz .-1"),
+            Instruction::BranchZero(Address::Relative(-1))
+        );
+        assert_eq!(single("lda . + 3"), Instruction::Load(Address::Relative(3)));
+
+        assert!(parse_src("bra .+").is_err());
+        assert!(parse_src("bra .-x").is_err());
+        assert!(parse_src("bra .+1-1").is_err());
+        assert!(parse_src("bra .+99").is_ok());
+        assert_eq!(
+            parse_src("nop\nbra .+9223372036854775807")
+                .unwrap_err()
+                .1
+                .to_string(),
+            "error @ line 2: offset .+9223372036854775807 is out of range: must be below 100"
+        );
+        assert_eq!(
+            parse_src("bra .-9223372036854775808")
+                .unwrap_err()
+                .1
+                .to_string(),
+            "error @ line 1: offset .-9223372036854775808 is out of range: must be below 100"
+        );
+        assert!(parse_src("bra label-1").is_err());
+        assert!(parse_src("bra 5-1").is_err());
+        assert!(parse_src(".").is_err());
+        assert!(parse_src("dat .").is_err());
+    }
+
     #[test]
     fn parse_without_addr() {
         assert_eq!(single("inp"), Instruction::Input);
         assert_eq!(single("out"), Instruction::Output);
         assert_eq!(single("hlt"), Instruction::Halt);
+        assert_eq!(single("nop"), Instruction::Nop);
+    }
+
+    #[test]
+    fn parse_with_device() {
+        assert_eq!(single("inp 0"), Instruction::InputDevice(0));
+        assert_eq!(single("inp 3"), Instruction::InputDevice(3));
+        assert_eq!(single("out 9"), Instruction::OutputDevice(9));
+
+        assert!(parse_src("inp 10").is_err());
+        assert!(parse_src("out 1 2").is_err());
+        assert!(parse_src("out a_label").is_err());
+        assert!(parse_src("hlt 1").is_err());
+        assert!(parse_src("nop 1").is_err());
     }
 
     #[test]
     fn parse_data() {
         assert_eq!(single("dat 123"), Instruction::Data(123));
+        assert_eq!(single("dat 5"), Instruction::Data(5));
+        assert_eq!(single("dat"), Instruction::Data(0));
+        assert_eq!(single("dat ; zero"), Instruction::Data(0));
+    }
+
+    #[test]
+    fn parse_bare_data_with_label() {
+        let info = parse_src("lda count\nhlt\ncount: dat\nnext: dat 5\nlast: dat").unwrap();
+
+        assert_eq!(
+            info.instructions[2..],
+            [
+                Instruction::Data(0),
+                Instruction::Data(5),
+                Instruction::Data(0)
+            ]
+        );
+        assert_eq!(info.label_map["count"], 2);
+        assert_eq!(info.label_map["next"], 3);
+        assert_eq!(info.label_map["last"], 4);
+        assert!(parse_src("dat ,").is_err());
+    }
+
+    #[test]
+    fn parse_data_list() {
+        let info = parse_src("table: dat 1, 2,3 , 999\nafter: hlt").unwrap();
+        let expected = vec![
+            Instruction::Data(1),
+            Instruction::Data(2),
+            Instruction::Data(3),
+            Instruction::Data(999),
+            Instruction::Halt,
+        ];
+
+        assert_eq!(info.instructions, expected);
+        assert_eq!(info.label_map.get("table"), Some(&0));
+        assert_eq!(info.label_map.get("after"), Some(&4));
+
+        assert!(parse_src("dat 1,").is_err());
+        assert!(parse_src("dat 1,,2").is_err());
+        assert!(parse_src("dat ,1").is_err());
+        assert!(parse_src("dat 1, 1000").is_err());
+        assert!(parse_src("dat 1, a_label").is_err());
     }
 
     #[test]
@@ -395,6 +1121,34 @@ mod tests {
         assert_eq!(info.instructions, expected);
     }
 
+    #[test]
+    fn parse_statements_on_one_line() {
+        use Address::{Numeric, Symbolic};
+
+        let source = "
+            loop: inp | brz end | out | bra loop
+            end: hlt |";
+        let info = parse_src(source).unwrap();
+        let expected = vec![
+            Instruction::Input,
+            Instruction::BranchZero(Symbolic("end".into())),
+            Instruction::Output,
+            Instruction::BranchAlways(Symbolic("loop".into())),
+            Instruction::Halt,
+        ];
+
+        assert_eq!(info.instructions, expected);
+        assert_eq!(info.label_map["end"], 4);
+        assert!(parse_src("inp out").is_err());
+        assert_eq!(
+            parse_src("lda 5 | sto 6").unwrap().instructions,
+            [
+                Instruction::Load(Numeric(5)),
+                Instruction::Store(Numeric(6))
+            ]
+        );
+    }
+
     #[test]
     fn fails_on_bad_ops() {
         // kw as addr
@@ -425,6 +1179,80 @@ mod tests {
         assert!(parse_src("dat 123 456").is_err());
     }
 
+    #[test]
+    fn names_tokens_in_errors() {
+        let err = |source| parse_src(source).unwrap_err().1.to_string();
+
+        assert_eq!(
+            err("lda add"),
+            "error @ line 1: expected an address but found keyword 'add'"
+        );
+        assert_eq!(
+            err("bra out"),
+            "error @ line 1: expected an address but found keyword 'out'"
+        );
+        assert_eq!(
+            err("lda 0 a_label"),
+            "error @ line 1: expected end of line but found label 'a_label'"
+        );
+        assert_eq!(
+            err("dat hlt"),
+            "error @ line 1: expected a number but found keyword 'hlt'"
+        );
+    }
+
+    #[test]
+    fn reports_line_of_bad_token() {
+        let source = "
+            inp
+            sto 10
+
+            lda add
+            hlt";
+
+        let (_, e) = parse_src(source).unwrap_err();
+        assert!(
+            matches!(e.errors()[0], LNCError::Parse { line: 5, .. }),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn reports_operand_line() {
+        let tokens = [
+            Token {
+                kind: TokenKind::Input,
+                line: 1,
+            },
+            Token {
+                kind: TokenKind::NewLine,
+                line: 1,
+            },
+            Token {
+                kind: TokenKind::Load,
+                line: 2,
+            },
+            Token {
+                kind: TokenKind::Add,
+                line: 3,
+            },
+            Token {
+                kind: TokenKind::NewLine,
+                line: 3,
+            },
+            Token {
+                kind: TokenKind::Eof,
+                line: 3,
+            },
+        ];
+
+        let (_, e) = parse(&tokens, 100).unwrap_err();
+        assert!(
+            matches!(e.errors()[0], LNCError::Parse { line: 3, .. }),
+            "{e}"
+        );
+    }
+
     #[test]
     fn maps_label_addr() {
         let src = "
@@ -453,6 +1281,111 @@ mod tests {
         assert_eq!(info.label_map, expected);
     }
 
+    #[test]
+    fn call_and_ret_errors() {
+        let err = |source| parse_src(source).unwrap_err().1.to_string();
+
+        assert_eq!(
+            err("ret"),
+            "error @ line 1: ret is not inside a subroutine (no label used by call comes before it)"
+        );
+        assert_eq!(
+            err("call twice\nhlt\ntwice: hlt"),
+            "error @ line 1: subroutine 'twice' has no ret"
+        );
+        assert_eq!(
+            err("call twice\nhlt\ntwice: ret\nret"),
+            "error @ line 4: subroutine 'twice' already returns at address 7"
+        );
+        assert_eq!(
+            err("call 5"),
+            "error @ line 1: expected a subroutine label but found number 5"
+        );
+
+        // labels inside a subroutine that aren't called don't own its ret
+        let info = parse_src("call twice\nhlt\ntwice: inp\nloop: brz loop\nret").unwrap();
+        assert_eq!(info.label_map["twice.ret"], 9);
+        assert_eq!(info.vars, ["call.acc"]);
+    }
+
+    #[test]
+    fn fails_on_duplicate_label() {
+        let src = "
+        loop: inp
+        out
+        loop: bra loop
+        hlt";
+
+        let (info, e) = parse_src(src).unwrap_err();
+
+        assert_eq!(
+            e.to_string(),
+            "error @ line 4: label 'loop' already defined at address 0"
+        );
+        assert_eq!(info.label_map.get("loop"), Some(&0));
+
+        // parsing continues after the duplicate
+        assert_eq!(info.instructions.len(), 3);
+    }
+
+    #[test]
+    fn parse_immediate_operands() {
+        let info = parse_src("ldi 5\naddi #12\nsubi 5\nhlt").unwrap();
+
+        assert_eq!(
+            info.instructions,
+            vec![
+                Instruction::Load(Address::Symbolic("const.5".into())),
+                Instruction::Add(Address::Symbolic("const.12".into())),
+                Instruction::Subtract(Address::Symbolic("const.5".into())),
+                Instruction::Halt,
+            ]
+        );
+        // each value gets one cell however often it is used
+        assert_eq!(info.consts, [5, 12]);
+        assert_eq!(info.const_lines, [1, 2]);
+
+        let err = |source| parse_src(source).unwrap_err().1.to_string();
+
+        assert_eq!(
+            err("addi x"),
+            "error @ line 1: expected a number but found label 'x'"
+        );
+        assert_eq!(
+            err("ldi 1000"),
+            "error @ line 1: invalid data 1000: too large"
+        );
+        assert!(parse_src("ldi 5 6").is_err());
+    }
+
+    #[test]
+    fn parse_vars() {
+        let src = "
+        lda count
+        count var
+        hlt
+        total var";
+
+        let info = parse_src(src).unwrap();
+
+        assert_eq!(info.vars, vec!["count".to_owned(), "total".to_owned()]);
+        assert_eq!(info.instructions.len(), 2);
+        assert!(info.label_map.is_empty());
+
+        assert!(parse_src("var").is_err());
+        assert_eq!(parse_src("COUNT VAR").unwrap().vars, ["COUNT"]);
+
+        // `var` can still be used as a label
+        let info = parse_src("lda var\nhlt\nvar: dat 0\nvar2 var").unwrap();
+        assert_eq!(info.label_map["var"], 2);
+        assert_eq!(info.vars, ["var2"]);
+        assert_eq!(parse_src("var var").unwrap().vars, ["var"]);
+        assert!(parse_src("x var 5").is_err());
+        assert!(parse_src("x var\nx var").is_err());
+        assert!(parse_src("x: hlt\nx var").is_err());
+        assert!(parse_src("x var\nx: hlt").is_err());
+    }
+
     #[test]
     fn parse_number_list() {
         assert_eq!(get_nlist("[]").unwrap(), vec![]);
@@ -492,8 +1425,134 @@ mod tests {
             make_test("_test123", vec![1, 2, 3], vec![1, 2, 3]),
         );
 
+        assert_eq!(
+            get_test(".test_name [1, 2] !"),
+            LNCTest {
+                name: "test_name".into(),
+                inputs: vec![1, 2],
+                outputs: vec![],
+                expect_error: true,
+                mem_checks: vec![],
+                skipped: false,
+            },
+        );
+        assert!(parse_src(".test_name [1] [2] !").is_err());
+        assert!(parse_src(".test_name ! [1]").is_err());
+        assert!(parse_src(".test_name !").is_err());
+
         assert!(parse_src(".test_name").is_err());
-        assert!(parse_src(".test_name [1, 2, 3]").is_err());
+        assert_eq!(
+            parse_src(".foo; comment").unwrap_err().1.to_string(),
+            "error @ line 1: test 'foo' needs an output list, e.g. .foo [3] or .foo [1, 2] [3]"
+        );
+        assert_eq!(
+            get_test(".test_name [1, 2, 3]"),
+            make_test("test_name", vec![], vec![1, 2, 3]),
+        );
+        assert_eq!(
+            get_test(".test_name []; no inputs or outputs"),
+            make_test("test_name", vec![], vec![]),
+        );
         assert!(parse_src(".test_name [1, 2, 3] [1, 2, 3] [1, 2, 3]").is_err());
     }
+
+    #[test]
+    fn rejects_duplicate_test_names() {
+        let (info, err) = parse_src("hlt\n.foo [1]\n.bar [1]\n.foo [2]").unwrap_err();
+
+        assert_eq!(err.to_string(), "error @ line 4: duplicate test name 'foo'");
+        assert_eq!(info.tests.len(), 2);
+
+        let tokens = tokenize(".a [1]\n.a [1]").unwrap();
+        assert!(parse_tests(&tokens).is_err());
+        assert!(parse_src(".foo [1]\n.foo_2 [1]").is_ok());
+    }
+
+    #[test]
+    fn parse_skipped_tests() {
+        assert!(get_test(".foo [1] [2] skip").skipped);
+        assert!(get_test(".foo [2] skip ; not yet").skipped);
+        assert!(get_test(".foo [1] ! skip").skipped);
+        assert!(get_test(".foo [] [4] mem[0]=4 skip").skipped);
+        assert!(!get_test(".foo [1] [2]").skipped);
+
+        assert!(parse_src(".foo [1] [2] skip skip").is_err());
+        assert!(parse_src(".foo [1] [2] skip mem[0]=4").is_err());
+    }
+
+    #[test]
+    fn parse_mem_checks() {
+        assert_eq!(get_test(".foo [1, 2] [] mem[50]=3").mem_checks, [(50, 3)]);
+        assert_eq!(
+            get_test(".foo [4] mem[0] = 4 mem[99]=999; comment"),
+            LNCTest {
+                mem_checks: vec![(0, 4), (99, 999)],
+                ..make_test("foo", vec![], vec![4])
+            },
+        );
+
+        assert!(parse_src(".foo [] [] mem[100]=3").is_err());
+        assert!(parse_src(".foo [] [] mem[1]=1000").is_err());
+        assert!(parse_src(".foo [] [] mem[1]").is_err());
+        assert!(parse_src(".foo [] [] mem 1=2").is_err());
+        assert!(parse_src(".foo [] [] mem[x]=2").is_err());
+        assert!(parse_src(".foo [] [] memory[1]=2").is_err());
+        assert!(parse_src(".foo [] ! mem[1]=2").is_err());
+        assert_eq!(
+            parse_src(".foo [] [] mem[1]=").unwrap_err().1.to_string(),
+            "error @ line 1: expected value but found end of line"
+        );
+    }
+
+    #[test]
+    fn parse_tests_file() {
+        let tokens = tokenize("; tests for double\n.one [1] [2]\n\n.none [0]\n").unwrap();
+
+        assert_eq!(
+            parse_tests(&tokens).unwrap(),
+            [
+                make_test("one", vec![1], vec![2]),
+                make_test("none", vec![], vec![0]),
+            ]
+        );
+
+        let tokens = tokenize(".one [1] [2]\ninp\n.two [2] [4] [6]").unwrap();
+        let err = parse_tests(&tokens).unwrap_err();
+
+        // parsing continues after the misplaced instruction
+        assert_eq!(err.errors().len(), 2);
+        assert_eq!(
+            err.errors()[0].to_string(),
+            "error @ line 2: expected a test definition but found keyword 'inp'"
+        );
+    }
+
+    #[test]
+    fn no_trailing_newline() {
+        for source in ["hlt", "inp\nadd 5", ".t [1] [2]", "x: dat 1, 2", "inp 0"] {
+            let without = parse_src(source).unwrap();
+            let with = parse_src(&format!("{source}\n")).unwrap();
+
+            assert_eq!(without.instructions, with.instructions, "{source:?}");
+            assert_eq!(without.tests, with.tests, "{source:?}");
+        }
+
+        assert_eq!(parse_src("inp\nadd 5").unwrap().lines, [1, 2]);
+
+        // an error at the end of a line doesn't hide one on the next line
+        let (_, err) = parse_src("lda\nfoo\ndat 1,\nbar\n.t [1\nbaz").unwrap_err();
+        let lines: Vec<_> = err
+            .errors()
+            .iter()
+            .map(|e| match e {
+                LNCError::Parse { line, .. } => *line,
+                _ => 0,
+            })
+            .collect();
+        assert_eq!(lines, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            parse_src("inp\nadd").unwrap_err().1.to_string(),
+            "error @ line 2: expected an address but found end of line"
+        );
+    }
 }
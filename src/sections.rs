@@ -0,0 +1,115 @@
+// Splits a file holding several programs, each starting with a marker line
+// like `=== adder ===`, into one source per program.
+
+pub struct Section {
+    pub name: String,
+    // padded with blank lines, so errors give line numbers in the whole file
+    pub source: String,
+}
+
+// The name in a marker line, `Some("")` for a bare `===`.
+fn marker_name(line: &str) -> Option<&str> {
+    let name = line.trim().strip_prefix("===")?;
+    Some(name.strip_suffix("===").unwrap_or(name).trim())
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with(';') || line.starts_with("//")
+}
+
+pub fn split(source: &str) -> Result<Vec<Section>, String> {
+    let mut sections: Vec<Section> = vec![];
+
+    for (i, line) in source.lines().enumerate() {
+        let line_num = i + 1;
+
+        if let Some(name) = marker_name(line) {
+            if name.is_empty() {
+                return Err(format!("line {line_num}: section marker has no name"));
+            }
+            if sections.iter().any(|s| s.name == name) {
+                return Err(format!("line {line_num}: duplicate section name '{name}'"));
+            }
+
+            sections.push(Section {
+                name: name.to_owned(),
+                source: "\n".repeat(line_num),
+            });
+            continue;
+        }
+
+        match sections.last_mut() {
+            Some(section) => {
+                section.source.push_str(line);
+                section.source.push('\n');
+            }
+            None if is_blank_or_comment(line) => (),
+            None => {
+                return Err(format!(
+                    "line {line_num}: expected a section marker like `=== name ===` before any code"
+                ))
+            }
+        }
+    }
+
+    if sections.is_empty() {
+        return Err("no sections found: start each program with a line like `=== name ===`".into());
+    }
+
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_markers() {
+        let source = "; examples
+=== adder ===
+inp
+add one
+out
+one: dat 1
+===doubler
+inp
+sto x
+add x
+out
+x: dat
+";
+        let sections = split(source).unwrap();
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].name, "adder");
+        assert_eq!(sections[1].name, "doubler");
+        assert_eq!(
+            sections[1].source,
+            "\n\n\n\n\n\n\ninp\nsto x\nadd x\nout\nx: dat\n"
+        );
+
+        // line numbers are those of the whole file
+        let program = crate::make_program(&sections[0].source).unwrap();
+        assert_eq!(program.parse_info.lines, [3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn rejects_bad_markers() {
+        let err = |source| split(source).err().unwrap();
+
+        assert_eq!(
+            err("inp\n=== a ==="),
+            "line 1: expected a section marker like `=== name ===` before any code"
+        );
+        assert_eq!(
+            err("=== a ===\nhlt\n==="),
+            "line 3: section marker has no name"
+        );
+        assert_eq!(
+            err("=== a ===\n=== a ==="),
+            "line 2: duplicate section name 'a'"
+        );
+        assert!(err("").starts_with("no sections found"));
+    }
+}
@@ -0,0 +1,60 @@
+// Helpers shared by the unit tests of several modules.
+
+use crate::interpreter::{Interpreter, InterpreterState};
+use crate::vec_io::{BufferLogger, QueueInput, StackOutput};
+use crate::LNCError;
+
+type TestInterpreter<'a> = Interpreter<'a, QueueInput, StackOutput, BufferLogger>;
+
+pub struct Run {
+    pub result: Result<usize, LNCError>,
+    pub outputs: Vec<usize>,
+    pub state: InterpreterState,
+    pub logs: Vec<String>,
+    pub ins_count: usize,
+    pub overflow_count: usize,
+    pub underflow_count: usize,
+    pub max_acc: usize,
+}
+
+// Assembles `source` and runs it to completion with the given inputs,
+// panicking if it doesn't assemble or errors at runtime.
+pub fn run_source(source: &str, inputs: &[usize]) -> Run {
+    let program = crate::make_program(source).unwrap();
+
+    let run = run_mem(program.mem, inputs, |interpreter| interpreter);
+    assert!(run.result.is_ok(), "{:?}", run.result);
+    run
+}
+
+// Runs `mem` with the given inputs until it halts, errors or hits the step
+// limit, after `build` has had a chance to configure the interpreter.
+pub fn run_mem(
+    mem: [usize; 100],
+    inputs: &[usize],
+    build: impl for<'a> FnOnce(TestInterpreter<'a>) -> TestInterpreter<'a>,
+) -> Run {
+    let mut input = QueueInput::new(inputs).unwrap();
+    let mut output = StackOutput::default();
+    let mut logger = BufferLogger::default();
+
+    let mut interpreter = build(Interpreter::new(mem, &mut input, &mut output, &mut logger));
+    let result = interpreter.run_to_halt(10_000);
+    let state = interpreter.state();
+    let ins_count = interpreter.ins_count();
+    let overflow_count = interpreter.overflow_count();
+    let underflow_count = interpreter.underflow_count();
+    let max_acc = interpreter.max_acc();
+    drop(interpreter);
+
+    Run {
+        result,
+        outputs: output.stack,
+        state,
+        logs: logger.messages,
+        ins_count,
+        overflow_count,
+        underflow_count,
+        max_acc,
+    }
+}
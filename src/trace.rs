@@ -0,0 +1,114 @@
+use std::io::{self, Write};
+
+use crate::disasm;
+use crate::interpreter::{Log, TraceEntry};
+
+pub struct TraceLogger<W: Write, L: Log> {
+    writer: W,
+    inner: L,
+    error: Option<io::Error>,
+}
+
+impl<W: Write, L: Log> TraceLogger<W, L> {
+    pub fn new(mut writer: W, inner: L) -> io::Result<Self> {
+        writeln!(writer, "ins,pc,code,mnemonic,acc_before,acc_after,neg_flag")?;
+
+        Ok(Self {
+            writer,
+            inner,
+            error: None,
+        })
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write, L: Log> Log for TraceLogger<W, L> {
+    fn log(&mut self, msg: String) {
+        self.inner.log(msg);
+    }
+
+    fn enabled(&self) -> bool {
+        self.inner.enabled()
+    }
+
+    fn explain(&mut self, msg: String) {
+        self.inner.explain(msg);
+    }
+
+    fn watch(&mut self, msg: String) {
+        self.inner.watch(msg);
+    }
+
+    fn warn(&mut self, msg: String) {
+        self.inner.warn(msg);
+    }
+
+    fn trace(&mut self, entry: &TraceEntry) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let res = writeln!(
+            self.writer,
+            "{},{},{:03},{},{},{},{}",
+            entry.ins,
+            entry.pc,
+            entry.code,
+            disasm::mnemonic(entry.code),
+            entry.acc_before,
+            entry.acc_after,
+            entry.neg_flag
+        );
+
+        if let Err(e) = res {
+            self.error = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::vec_io::{NullLogger, QueueInput, StackOutput};
+
+    #[test]
+    fn writes_trace_rows() {
+        let program = crate::make_program(
+            "
+            inp
+            sub one
+            out
+            hlt
+            one: dat 1",
+        )
+        .unwrap();
+
+        let mut input = QueueInput::new(&[0]).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = TraceLogger::new(vec![], NullLogger).unwrap();
+
+        let mut interpreter = Interpreter::new(program.mem, &mut input, &mut output, &mut logger);
+        interpreter.run_to_halt(100).unwrap();
+
+        let trace = String::from_utf8(logger.finish().unwrap()).unwrap();
+        let expected = "\
+ins,pc,code,mnemonic,acc_before,acc_after,neg_flag
+0,0,901,inp,0,0,false
+1,1,204,sub 04,0,999,true
+2,2,902,out,999,999,true
+3,3,000,hlt,999,999,true
+";
+
+        assert_eq!(trace, expected);
+    }
+}
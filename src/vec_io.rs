@@ -1,25 +1,38 @@
 use std::collections::VecDeque;
+use std::io::Write;
 
-use crate::interpreter::{Input, LNCInput, Output};
+use crate::interpreter::{Input, LNCInput, Log, Output};
+
+pub const EMPTY_QUEUE: &str = "error: input queue is empty!";
 
 #[derive(Default)]
 pub struct QueueInput {
     pub queue: VecDeque<LNCInput>,
+    // how many inputs the queue started with
+    pub provided: usize,
 }
 
 impl QueueInput {
     pub fn new(nums: &[usize]) -> Result<Self, String> {
+        Self::with_limit(nums, 1000)
+    }
+
+    // every number must be below `limit`
+    pub fn with_limit(nums: &[usize], limit: usize) -> Result<Self, String> {
         let mut queue = VecDeque::new();
 
         for num in nums {
-            if let Some(lnc_num) = LNCInput::new(*num) {
+            if let Some(lnc_num) = LNCInput::with_limit(*num, limit) {
                 queue.push_back(lnc_num);
             } else {
                 return Err(format!("error: input number ({num}) is too large"));
             }
         }
 
-        Ok(Self { queue })
+        Ok(Self {
+            provided: queue.len(),
+            queue,
+        })
     }
 }
 
@@ -28,7 +41,22 @@ impl Input for QueueInput {
         if let Some(lnc_num) = self.queue.pop_front() {
             Ok(lnc_num)
         } else {
-            Err("error: input queue is empty!".into())
+            Err(EMPTY_QUEUE.into())
+        }
+    }
+}
+
+// Takes the queued values first and only asks `fallback` once they run out.
+pub struct PrefilledInput<I: Input> {
+    pub queue: QueueInput,
+    pub fallback: I,
+}
+
+impl<I: Input> Input for PrefilledInput<I> {
+    fn take(&mut self) -> Result<LNCInput, String> {
+        match self.queue.queue.pop_front() {
+            Some(lnc_num) => Ok(lnc_num),
+            None => self.fallback.take(),
         }
     }
 }
@@ -43,3 +71,310 @@ impl Output for StackOutput {
         self.stack.push(val);
     }
 }
+
+// Checks each output against the next expected value as it is sent. Only the
+// first mismatch is kept, so a run can be stopped as soon as `mismatch` is set.
+pub struct AssertOutput {
+    expected: Vec<usize>,
+    // how many values have been sent
+    pos: usize,
+    mismatch: Option<String>,
+}
+
+impl AssertOutput {
+    pub fn new(expected: &[usize]) -> Self {
+        Self {
+            expected: expected.to_vec(),
+            pos: 0,
+            mismatch: None,
+        }
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn mismatch(&self) -> Option<&str> {
+        self.mismatch.as_deref()
+    }
+
+    // every expected value was sent, and nothing else
+    pub fn is_complete(&self) -> bool {
+        self.mismatch.is_none() && self.pos == self.expected.len()
+    }
+}
+
+impl Output for AssertOutput {
+    fn send(&mut self, val: usize) {
+        self.pos += 1;
+        if self.mismatch.is_some() {
+            return;
+        }
+
+        // counted from 1 in messages
+        let n = self.pos;
+        self.mismatch = match self.expected.get(n - 1) {
+            Some(&expected) if expected == val => None,
+            Some(&expected) => Some(format!("output {n}: expected {expected} but got {val}")),
+            None => {
+                let len = self.expected.len();
+                let s = if len == 1 { "" } else { "s" };
+                Some(format!(
+                    "output {n}: expected only {len} output{s} but got {val}"
+                ))
+            }
+        };
+    }
+}
+
+// Writes each value on its own line to any `Write` sink: a file, a pipe or a
+// `Vec<u8>`. Write errors are ignored since `Output::send` can't report them.
+pub struct WriterOutput<W: Write> {
+    pub writer: W,
+    pub signed: bool,
+}
+
+impl<W: Write> WriterOutput<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            signed: false,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Output for WriterOutput<W> {
+    fn send(&mut self, val: usize) {
+        let _ = writeln!(self.writer, "{}", format_value(val, self.signed));
+    }
+}
+
+// Discards every log message, without the interpreter formatting them.
+pub struct NullLogger;
+
+impl Log for NullLogger {
+    fn log(&mut self, _msg: String) {}
+
+    fn enabled(&self) -> bool {
+        false
+    }
+}
+
+// Keeps every log message so it can be inspected after a run.
+#[derive(Default)]
+pub struct BufferLogger {
+    pub messages: Vec<String>,
+}
+
+impl Log for BufferLogger {
+    fn log(&mut self, msg: String) {
+        self.messages.push(msg);
+    }
+}
+
+// Values of 500 and above are treated as the negative numbers -500 to -1,
+// i.e. the ten's complement of the value.
+pub fn signed(val: usize) -> isize {
+    if val >= 500 {
+        val as isize - 1000
+    } else {
+        val as isize
+    }
+}
+
+pub fn format_value(val: usize, signed_output: bool) -> String {
+    if signed_output {
+        signed(val).to_string()
+    } else {
+        val.to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct BufferedOutput {
+    pub history: Vec<usize>,
+    pub signed: bool,
+    flushed: usize,
+}
+
+impl BufferedOutput {
+    pub fn new(signed: bool) -> Self {
+        Self {
+            signed,
+            ..Default::default()
+        }
+    }
+
+    fn pending(&self) -> String {
+        self.history[self.flushed..]
+            .iter()
+            .map(|&val| format_value(val, self.signed))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn flush(&mut self) {
+        if self.flushed < self.history.len() {
+            println!("Output: {}", self.pending());
+            self.flushed = self.history.len();
+        }
+    }
+}
+
+impl Output for BufferedOutput {
+    fn send(&mut self, val: usize) {
+        self.history.push(val);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_values() {
+        assert_eq!(format_value(997, true), "-3");
+        assert_eq!(format_value(3, true), "3");
+        assert_eq!(format_value(499, true), "499");
+        assert_eq!(format_value(500, true), "-500");
+        assert_eq!(format_value(999, true), "-1");
+        assert_eq!(format_value(0, true), "0");
+
+        assert_eq!(format_value(997, false), "997");
+        assert_eq!(format_value(3, false), "3");
+    }
+
+    #[test]
+    fn prefilled_input_falls_back() {
+        let mut input = PrefilledInput {
+            queue: QueueInput::new(&[5, 10]).unwrap(),
+            fallback: QueueInput::new(&[7]).unwrap(),
+        };
+
+        let mut take = || input.take().map(usize::from);
+        assert_eq!(take(), Ok(5));
+        assert_eq!(take(), Ok(10));
+        assert_eq!(take(), Ok(7));
+        assert_eq!(take(), Err(EMPTY_QUEUE.into()));
+        assert_eq!(input.queue.provided, 2);
+    }
+
+    #[test]
+    fn queue_input_limits() {
+        assert_eq!(
+            QueueInput::new(&[1, 1000]).err(),
+            Some("error: input number (1000) is too large".into())
+        );
+        assert!(QueueInput::with_limit(&[9999], 10_000).is_ok());
+    }
+
+    #[test]
+    fn writer_output_to_vec() {
+        let mut output = WriterOutput::new(vec![]);
+        output.send(3);
+        output.send(999);
+        assert_eq!(output.writer, b"3\n999\n");
+
+        let mut output = WriterOutput {
+            signed: true,
+            ..WriterOutput::new(vec![])
+        };
+        output.send(3);
+        output.send(999);
+        assert_eq!(String::from_utf8(output.into_inner()).unwrap(), "3\n-1\n");
+    }
+
+    #[test]
+    fn assert_output_matches() {
+        let mut output = AssertOutput::new(&[1, 2]);
+        output.send(1);
+        assert!(!output.is_complete());
+        output.send(2);
+
+        assert_eq!(output.mismatch(), None);
+        assert!(output.is_complete());
+    }
+
+    #[test]
+    fn assert_output_keeps_first_mismatch() {
+        let mut output = AssertOutput::new(&[1, 2, 3]);
+        output.send(1);
+        output.send(5);
+        output.send(4);
+
+        assert_eq!(output.mismatch(), Some("output 2: expected 2 but got 5"));
+        assert_eq!(output.pos(), 3);
+        assert!(!output.is_complete());
+
+        let mut output = AssertOutput::new(&[1]);
+        output.send(1);
+        output.send(1);
+        assert_eq!(
+            output.mismatch(),
+            Some("output 2: expected only 1 output but got 1")
+        );
+
+        let mut output = AssertOutput::new(&[1, 2]);
+        for val in [1, 2, 3] {
+            output.send(val);
+        }
+        assert_eq!(
+            output.mismatch(),
+            Some("output 3: expected only 2 outputs but got 3")
+        );
+    }
+
+    #[test]
+    fn assert_output_stops_a_run_early() {
+        use crate::interpreter::Interpreter;
+
+        let mut input = QueueInput::default();
+        let mut output = AssertOutput::new(&[3, 2, 1]);
+        let mut logger = NullLogger;
+
+        // lda 04, loop: out, add 05, bra loop, dat 3, dat 1: counts up forever
+        let mut mem = [0; 100];
+        mem[..6].copy_from_slice(&[504, 902, 105, 601, 3, 1]);
+        let mut interpreter = Interpreter::new(mem, &mut input, &mut output, &mut logger);
+
+        while !interpreter.is_halted() && interpreter.output().mismatch().is_none() {
+            interpreter.step().unwrap();
+        }
+        assert_eq!(interpreter.output().pos(), 2);
+        drop(interpreter);
+        assert_eq!(output.mismatch(), Some("output 2: expected 2 but got 4"));
+    }
+
+    #[test]
+    fn buffered_signed_output() {
+        let mut output = BufferedOutput::new(true);
+
+        output.send(1);
+        output.send(0);
+        output.send(999);
+        assert_eq!(output.pending(), "1, 0, -1");
+        assert_eq!(output.history, vec![1, 0, 999]);
+    }
+
+    #[test]
+    fn buffered_output_groups_values() {
+        let mut output = BufferedOutput::default();
+        assert_eq!(output.pending(), "");
+
+        output.send(3);
+        output.send(2);
+        output.send(1);
+        assert_eq!(output.pending(), "3, 2, 1");
+
+        output.flush();
+        assert_eq!(output.pending(), "");
+
+        output.send(0);
+        assert_eq!(output.pending(), "0");
+        assert_eq!(output.history, vec![3, 2, 1, 0]);
+    }
+}
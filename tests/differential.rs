@@ -0,0 +1,284 @@
+// Runs randomly generated programs through the interpreter and a deliberately
+// simple reference implementation of classic LMC, comparing the two after
+// every instruction. Along the way it checks that:
+//
+// - the accumulator always holds 0-999, and every cell of memory does too
+// - the pc is 0-99 whenever an instruction is fetched, and running past
+//   address 99 is an error rather than a panic
+// - after `sub`, the negative flag is set exactly when the value subtracted
+//   was larger than the accumulator, and the accumulator wrapped around by 1000
+// - after `add`, the negative flag is clear and the sum wrapped modulo 1000
+// - both stop with an error on the same instruction (undefined instructions,
+//   running out of inputs, running off the end of memory)
+//
+// Failures print the seed of the program, so it can be re-run on its own.
+
+use std::collections::VecDeque;
+
+use lnc::interpreter::Interpreter;
+use lnc::vec_io::{NullLogger, QueueInput, StackOutput};
+use lnc::{encode, Address, Instruction};
+
+const CASES: u64 = 2000;
+const MAX_STEPS: usize = 200;
+
+// xorshift64*, so the programs are the same on every run
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // the state must never be 0
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+struct Case {
+    mem: [usize; 100],
+    inputs: Vec<usize>,
+}
+
+// Short programs of real instructions whose addresses mostly point into or
+// just past the program, followed by a few random data cells.
+fn generate(rng: &mut Rng) -> Case {
+    let len = 1 + rng.below(20);
+    let mut mem = [0; 100];
+
+    for cell in mem.iter_mut().take(len) {
+        let addr = if rng.below(4) == 0 {
+            rng.below(100)
+        } else {
+            rng.below(len + 5)
+        };
+        let addr = Address::Numeric(addr);
+
+        let ins = match rng.below(14) {
+            0 => Instruction::Load(addr),
+            1 => Instruction::Store(addr),
+            2 | 3 => Instruction::Add(addr),
+            4 | 5 => Instruction::Subtract(addr),
+            6 => Instruction::Input,
+            7 => Instruction::Output,
+            8 => Instruction::BranchZero(addr),
+            9 => Instruction::BranchPositive(addr),
+            10 => Instruction::BranchAlways(addr),
+            11 => Instruction::Nop,
+            12 => Instruction::Halt,
+            _ => Instruction::Data(rng.below(1000)),
+        };
+        *cell = encode(&ins).unwrap();
+    }
+
+    for cell in mem.iter_mut().skip(len).take(5) {
+        *cell = rng.below(1000);
+    }
+    // sometimes fill the end of memory so the pc can run off it
+    if rng.below(10) == 0 {
+        mem[90..].fill(900);
+    }
+
+    let inputs = (0..rng.below(6)).map(|_| rng.below(1000)).collect();
+
+    Case { mem, inputs }
+}
+
+// Classic LMC, written out as plainly as possible.
+struct Reference {
+    mem: [usize; 100],
+    pc: usize,
+    acc: usize,
+    neg_flag: bool,
+    halted: bool,
+    inputs: VecDeque<usize>,
+    outputs: Vec<usize>,
+}
+
+impl Reference {
+    // Returns false if the instruction is an error.
+    fn step(&mut self) -> bool {
+        if self.pc >= 100 {
+            return false;
+        }
+
+        let code = self.mem[self.pc];
+        let addr = code % 100;
+        self.pc += 1;
+
+        match code / 100 {
+            1 => {
+                self.acc = (self.acc + self.mem[addr]) % 1000;
+                self.neg_flag = false;
+            }
+            2 => {
+                self.neg_flag = self.mem[addr] > self.acc;
+                self.acc = (self.acc + 1000 - self.mem[addr]) % 1000;
+            }
+            3 => self.mem[addr] = self.acc,
+            5 => self.acc = self.mem[addr],
+            6 => self.pc = addr,
+            7 if self.acc == 0 => self.pc = addr,
+            8 if !self.neg_flag => self.pc = addr,
+            7 | 8 => (),
+            _ => match code {
+                0 => self.halted = true,
+                900 => (),
+                901 => match self.inputs.pop_front() {
+                    Some(value) => self.acc = value,
+                    None => return false,
+                },
+                902 => self.outputs.push(self.acc),
+                _ => return false,
+            },
+        }
+
+        true
+    }
+}
+
+fn check(seed: u64) {
+    let mut rng = Rng::new(seed);
+    let case = generate(&mut rng);
+
+    let mut reference = Reference {
+        mem: case.mem,
+        pc: 0,
+        acc: 0,
+        neg_flag: false,
+        halted: false,
+        inputs: case.inputs.iter().copied().collect(),
+        outputs: vec![],
+    };
+
+    let mut input = QueueInput::new(&case.inputs).unwrap();
+    let mut output = StackOutput::default();
+    let mut logger = NullLogger;
+    let mut interpreter = Interpreter::new(case.mem, &mut input, &mut output, &mut logger);
+
+    for step in 0..MAX_STEPS {
+        let before = interpreter.state();
+        if before.halted {
+            break;
+        }
+
+        let res = interpreter.step();
+        let ok = reference.step();
+        let state = interpreter.state();
+
+        assert_eq!(
+            res.is_ok(),
+            ok,
+            "seed {seed}, step {step}: interpreter returned {res:?}"
+        );
+        if !ok {
+            break;
+        }
+
+        assert!(
+            before.pc < 100,
+            "seed {seed}, step {step}: fetched at {}",
+            before.pc
+        );
+        assert!(
+            state.acc < 1000,
+            "seed {seed}, step {step}: acc is {}",
+            state.acc
+        );
+        assert!(
+            state.mem.iter().all(|&cell| cell < 1000),
+            "seed {seed}, step {step}"
+        );
+
+        let code = before.mem[before.pc];
+        let value = before.mem[code % 100];
+        match code / 100 {
+            1 => {
+                assert!(
+                    !state.neg_flag,
+                    "seed {seed}, step {step}: neg_flag after add"
+                );
+                assert_eq!(
+                    state.acc,
+                    (before.acc + value) % 1000,
+                    "seed {seed}, step {step}"
+                );
+            }
+            2 => {
+                assert_eq!(
+                    state.neg_flag,
+                    value > before.acc,
+                    "seed {seed}, step {step}"
+                );
+                assert_eq!(
+                    state.acc,
+                    (before.acc + 1000 - value) % 1000,
+                    "seed {seed}, step {step}"
+                );
+            }
+            _ => (),
+        }
+
+        assert_eq!(
+            (state.mem, state.pc, state.acc, state.neg_flag, state.halted),
+            (
+                reference.mem,
+                reference.pc,
+                reference.acc,
+                reference.neg_flag,
+                reference.halted
+            ),
+            "seed {seed}, step {step}: interpreter and reference disagree"
+        );
+    }
+
+    drop(interpreter);
+    assert_eq!(
+        output.stack, reference.outputs,
+        "seed {seed}: outputs differ"
+    );
+}
+
+#[test]
+fn interpreter_matches_reference() {
+    for seed in 0..CASES {
+        check(seed);
+    }
+}
+
+#[test]
+fn generated_programs_are_interesting() {
+    // make sure the generator isn't producing programs that all stop at once
+    let mut long_runs = 0;
+    let mut errors = 0;
+
+    for seed in 0..CASES {
+        let case = generate(&mut Rng::new(seed));
+
+        let mut input = QueueInput::new(&case.inputs).unwrap();
+        let mut output = StackOutput::default();
+        let mut logger = NullLogger;
+        let mut interpreter = Interpreter::new(case.mem, &mut input, &mut output, &mut logger);
+
+        if interpreter.run_to_halt(MAX_STEPS).is_err() {
+            errors += 1;
+        }
+        if interpreter.ins_count() >= 5 {
+            long_runs += 1;
+        }
+    }
+
+    assert!(
+        long_runs > CASES / 4,
+        "only {long_runs} programs ran 5+ instructions"
+    );
+    assert!(errors > 0 && errors < CASES, "{errors} programs errored");
+}